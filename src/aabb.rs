@@ -50,7 +50,41 @@ impl AABB {
         }
     }
 
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.x_interval.0 + self.x_interval.1) / 2.0,
+            (self.y_interval.0 + self.y_interval.1) / 2.0,
+            (self.z_interval.0 + self.z_interval.1) / 2.0,
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let [x, y, z] = [0, 1, 2].map(|axis| self[axis].1 - self[axis].0);
+        2.0 * (x * y + y * z + z * x)
+    }
+
     pub fn hit(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.clip(ray, t_range).is_some()
+    }
+
+    // 使用预先算好的方向倒数，供 BVH 遍历时对同一条光线反复测试
+    pub fn hit_inverse(&self, origin: Point3, inv_direction: Vec3, t_range: Vec2) -> bool {
+        let mut result = t_range;
+        for idx in 0..3 {
+            let interval = self[idx];
+            let t0 = (interval.0 - origin[idx]) * inv_direction[idx];
+            let t1 = (interval.1 - origin[idx]) * inv_direction[idx];
+            result.0 = result.0.max(t0.min(t1));
+            result.1 = result.1.min(t0.max(t1));
+            if result.0 >= result.1 {
+                return false;
+            }
+        }
+        true
+    }
+
+    // 返回光线在包围盒内的参数区间
+    pub fn clip(&self, ray: &Ray, t_range: Vec2) -> Option<Vec2> {
         let mut result = t_range;
         for idx in 0..3 {
             let interval = self[idx];
@@ -62,10 +96,10 @@ impl AABB {
             result.0 = result.0.max(t0);
             result.1 = result.1.min(t1);
             if result.0 >= result.1 {
-                return false;
+                return None;
             }
         }
-        true
+        Some(result)
     }
 }
 
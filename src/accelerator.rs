@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    bvh::{BvhNode, BvhOptions},
+    grid::{GridOptions, UniformGrid},
+    hittable::{HitRecord, Hittable},
+    kdtree::{KdTree, KdTreeOptions},
+    ray::Ray,
+    vec::Vec2,
+};
+
+pub trait Accelerator: Hittable {
+    // 切换到新的快门区间，返回是否重新构建
+    fn update(&mut self, shutter: Vec2) -> bool;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AcceleratorKind {
+    Bvh(BvhOptions),
+    Grid(GridOptions),
+    KdTree(KdTreeOptions),
+}
+
+pub enum AcceleratorEnum {
+    Bvh(BvhNode),
+    Grid(UniformGrid),
+    KdTree(KdTree),
+}
+
+impl AcceleratorEnum {
+    pub fn new(kind: &AcceleratorKind, list: &[Arc<dyn Hittable>]) -> Self {
+        match kind {
+            AcceleratorKind::Bvh(options) => AcceleratorEnum::Bvh(BvhNode::new(list, options)),
+            AcceleratorKind::Grid(options) => {
+                AcceleratorEnum::Grid(UniformGrid::new(list, options))
+            }
+            AcceleratorKind::KdTree(options) => AcceleratorEnum::KdTree(KdTree::new(list, options)),
+        }
+    }
+}
+
+impl Hittable for AcceleratorEnum {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        match self {
+            AcceleratorEnum::Bvh(accelerator) => accelerator.hit(ray, t_range),
+            AcceleratorEnum::Grid(accelerator) => accelerator.hit(ray, t_range),
+            AcceleratorEnum::KdTree(accelerator) => accelerator.hit(ray, t_range),
+        }
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        match self {
+            AcceleratorEnum::Bvh(accelerator) => accelerator.occluded(ray, t_range),
+            AcceleratorEnum::Grid(accelerator) => accelerator.occluded(ray, t_range),
+            AcceleratorEnum::KdTree(accelerator) => accelerator.occluded(ray, t_range),
+        }
+    }
+    fn bounding_box(&self) -> &AABB {
+        match self {
+            AcceleratorEnum::Bvh(accelerator) => accelerator.bounding_box(),
+            AcceleratorEnum::Grid(accelerator) => accelerator.bounding_box(),
+            AcceleratorEnum::KdTree(accelerator) => accelerator.bounding_box(),
+        }
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        match self {
+            AcceleratorEnum::Bvh(accelerator) => accelerator.bounding_box_during(shutter),
+            AcceleratorEnum::Grid(accelerator) => accelerator.bounding_box_during(shutter),
+            AcceleratorEnum::KdTree(accelerator) => accelerator.bounding_box_during(shutter),
+        }
+    }
+}
+
+impl Accelerator for AcceleratorEnum {
+    fn update(&mut self, shutter: Vec2) -> bool {
+        match self {
+            AcceleratorEnum::Bvh(accelerator) => accelerator.update(shutter),
+            AcceleratorEnum::Grid(accelerator) => accelerator.update(shutter),
+            AcceleratorEnum::KdTree(accelerator) => accelerator.update(shutter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        geometry::Sphere,
+        material::Lambertian,
+        random::m_random_range,
+        texture::SolidTexture,
+        vec::{Point3, Vec3},
+    };
+
+    // 随机球体上的随机光线，网格和 kd 树给出的最近交点必须与 BVH 一致
+    fn agrees_with_bvh(kind: AcceleratorKind) {
+        let material = Arc::new(Lambertian::new(SolidTexture::new(Color::from_single(0.5))));
+        let list: Vec<Arc<dyn Hittable>> = (0..200)
+            .map(|_| {
+                let center = Vec3::random_rage(-10.0..10.0);
+                let radius = m_random_range(0.1..1.5);
+                Arc::new(Sphere::new(center, center, radius, material.clone())) as Arc<dyn Hittable>
+            })
+            .collect();
+        let bvh = AcceleratorEnum::new(&AcceleratorKind::Bvh(BvhOptions::default()), &list);
+        let other = AcceleratorEnum::new(&kind, &list);
+        for _ in 0..20000 {
+            let origin: Point3 = Vec3::random_rage(-15.0..15.0);
+            let ray = Ray::new(origin, Vec3::random_rage(-1.0..1.0), 0.0);
+            let t_range = Vec2::new(0.001, m_random_range(1.0..40.0));
+            let expected = bvh.hit(&ray, t_range).map(|rec| rec.t);
+            let actual = other.hit(&ray, t_range).map(|rec| rec.t);
+            match (expected, actual) {
+                (Some(e), Some(a)) => assert!((e - a).abs() < 1e-9, "{kind:?}: {e} != {a}"),
+                _ => assert_eq!(expected, actual, "{kind:?}"),
+            }
+            assert_eq!(
+                bvh.occluded(&ray, t_range),
+                other.occluded(&ray, t_range),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn grid_agrees_with_bvh() {
+        agrees_with_bvh(AcceleratorKind::Grid(GridOptions::default()));
+    }
+
+    #[test]
+    fn kd_tree_agrees_with_bvh() {
+        agrees_with_bvh(AcceleratorKind::KdTree(KdTreeOptions::default()));
+    }
+}
@@ -1,73 +1,859 @@
-use std::sync::Arc;
+use std::{num::NonZero, sync::Arc, thread};
 
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable},
-    random::m_random_range,
+    accelerator::Accelerator,
+    cache::{CacheReader, CacheWriter},
+    hittable::{self, HitRecord, Hittable},
     ray::Ray,
-    vec::Vec2,
+    vec::{Point3, Vec2, Vec3},
 };
 
+#[derive(Debug, Clone, Copy)]
+pub struct BvhOptions {
+    // 叶子中图元数量的上限，超过时一定继续划分
+    pub max_leaf_size: usize,
+    // SAH 划分时每个轴上的桶数
+    pub bins: usize,
+    // 构建时使用的线程数
+    pub threads: usize,
+    // refit 后 SAH 代价超过构建时的这个倍数就重新构建
+    pub rebuild_threshold: f64,
+}
+
+impl Default for BvhOptions {
+    fn default() -> Self {
+        BvhOptions {
+            max_leaf_size: 4,
+            bins: 12,
+            threads: thread::available_parallelism().map_or(1, NonZero::get),
+            rebuild_threshold: 1.5,
+        }
+    }
+}
+
+// 扁平数组中的节点，内部节点的第一个孩子紧跟在它后面
+enum BvhContent {
+    // 图元在 primitives 中的区间
+    Leaf { start: usize, count: usize },
+    // second 为第二个孩子的下标，axis 为划分轴，用于按光线方向决定先访问哪边
+    Interior { second: usize, axis: usize },
+}
+
+struct LinearNode {
+    bbox: AABB,
+    content: BvhContent,
+}
+
+// 深度优先排列的扁平 BVH，用显式栈遍历
 pub struct BvhNode {
+    nodes: Vec<LinearNode>,
+    primitives: Vec<Arc<dyn Hittable>>,
+    options: BvhOptions,
+    // 最近一次构建后的 SAH 代价，用来判断 refit 后质量是否下降太多
+    build_cost: f64,
+}
+
+// 只由包围盒构建出的树结构，图元用其在输入中的下标表示，可以写入缓存文件
+pub struct BvhLayout {
+    nodes: Vec<LinearNode>,
+    // 叶子引用的区间在此数组中，值为图元在输入中的下标
+    order: Vec<usize>,
+}
+
+struct BuildItem {
     bbox: AABB,
-    left: Arc<dyn Hittable>,
-    right: Arc<dyn Hittable>,
+    index: usize,
+}
+
+// 遍历栈的大小，构建时超过 MAX_SAH_DEPTH 层后改为对半划分，保证深度不会超过它
+const STACK_SIZE: usize = 64;
+const MAX_SAH_DEPTH: usize = 32;
+// 图元数量达到该值的子树才考虑多线程构建
+const PARALLEL_THRESHOLD: usize = 16384;
+
+impl BvhNode {
+    // 由近到远遍历与光线相交的叶子，visit 返回新的 t 上界，返回 None 时提前结束
+    fn traverse<'a>(
+        &'a self,
+        ray: &Ray,
+        t_range: Vec2,
+        mut visit: impl FnMut(&'a [Arc<dyn Hittable>], Vec2) -> Option<f64>,
+    ) {
+        let inv_direction = Vec3::new(
+            1.0 / ray.direction.0,
+            1.0 / ray.direction.1,
+            1.0 / ray.direction.2,
+        );
+        let negative = [
+            inv_direction.0 < 0.0,
+            inv_direction.1 < 0.0,
+            inv_direction.2 < 0.0,
+        ];
+        let mut t_max = t_range.1;
+        let mut stack = [0; STACK_SIZE];
+        let mut top = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            let range = Vec2::new(t_range.0, t_max);
+            if node.bbox.hit_inverse(ray.origin, inv_direction, range) {
+                match node.content {
+                    BvhContent::Leaf { start, count } => {
+                        match visit(&self.primitives[start..start + count], range) {
+                            Some(t) => t_max = t,
+                            None => return,
+                        }
+                    }
+                    BvhContent::Interior { second, axis } => {
+                        // 先访问靠近光线起点的孩子，另一个压栈
+                        if negative[axis] {
+                            stack[top] = current + 1;
+                            current = second;
+                        } else {
+                            stack[top] = second;
+                            current += 1;
+                        }
+                        top += 1;
+                        continue;
+                    }
+                }
+            }
+            if top == 0 {
+                return;
+            }
+            top -= 1;
+            current = stack[top];
+        }
+    }
 }
 
 impl Hittable for BvhNode {
     fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
-        if !self.bbox.hit(ray, t_range) {
-            return None;
-        }
-        let hit_left = self.left.hit(ray, t_range);
-        let new_t_range = if let Some(ref rec) = hit_left {
-            Vec2::new(t_range.0, rec.t)
-        } else {
-            t_range
+        let mut result = None;
+        self.traverse(ray, t_range, |items, range| {
+            let mut closest_so_far = range.1;
+            for item in items {
+                if let Some(rec) = item.hit(ray, Vec2::new(range.0, closest_so_far)) {
+                    closest_so_far = rec.t;
+                    result = Some(rec);
+                }
+            }
+            Some(closest_so_far)
+        });
+        result
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let mut occluded = false;
+        self.traverse(ray, t_range, |items, range| {
+            occluded = items.iter().any(|item| item.occluded(ray, range));
+            (!occluded).then_some(range.1)
+        });
+        occluded
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.nodes[0].bbox
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        hittable::bounding_box_during(&self.primitives, shutter)
+    }
+}
+
+// 遍历一个内部节点的代价，相对于求交一个图元
+const TRAVERSAL_COST: f64 = 0.125;
+
+impl BvhLayout {
+    // 按表面积启发式（SAH）分桶构建
+    pub fn build(boxes: &[AABB], options: &BvhOptions) -> Self {
+        assert!(!boxes.is_empty(), "BVH has no primitives.");
+        let mut items: Vec<BuildItem> = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, bbox)| BuildItem {
+                bbox: bbox.clone(),
+                index,
+            })
+            .collect();
+        let mut builder = Builder {
+            options,
+            nodes: Vec::with_capacity(2 * items.len()),
         };
-        let hit_right = self.right.hit(ray, new_t_range);
-        match (hit_left, hit_right) {
-            (None, None) => None,
-            (Some(rec), None) => Some(rec),
-            (None, Some(rec)) => Some(rec),
-            (Some(left_rec), Some(right_rec)) => {
-                if left_rec.t < right_rec.t {
-                    Some(left_rec)
-                } else {
-                    Some(right_rec)
+        builder.build(&mut items, 0, 0);
+        BvhLayout {
+            nodes: builder.nodes,
+            order: items.into_iter().map(|item| item.index).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn write(&self, writer: &mut CacheWriter) {
+        writer.usize(self.nodes.len());
+        for node in &self.nodes {
+            for axis in 0..3 {
+                writer.f64(node.bbox[axis].0);
+                writer.f64(node.bbox[axis].1);
+            }
+            match node.content {
+                BvhContent::Leaf { start, count } => {
+                    writer.u64(0);
+                    writer.usize(start);
+                    writer.usize(count);
+                }
+                BvhContent::Interior { second, axis } => {
+                    writer.u64(1);
+                    writer.usize(second);
+                    writer.usize(axis);
                 }
             }
         }
+        writer.usize(self.order.len());
+        self.order.iter().for_each(|&index| writer.usize(index));
     }
-    fn bounding_box(&self) -> &AABB {
-        &self.bbox
+
+    // 除了读取失败，还检查下标是否越界，保证遍历时不会 panic
+    pub fn read(reader: &mut CacheReader) -> Option<Self> {
+        let nodes: Vec<LinearNode> = reader.vec(|r| {
+            let [x0, x1, y0, y1, z0, z1] = [(); 6].map(|_| r.f64());
+            let bbox = AABB::new(Point3::new(x0?, y0?, z0?), Point3::new(x1?, y1?, z1?));
+            let content = match (r.u64()?, r.usize()?, r.usize()?) {
+                (0, start, count) => BvhContent::Leaf { start, count },
+                (1, second, axis) if axis < 3 => BvhContent::Interior { second, axis },
+                _ => return None,
+            };
+            Some(LinearNode { bbox, content })
+        })?;
+        let order: Vec<usize> = reader.vec(CacheReader::usize)?;
+        if nodes.is_empty() || order.iter().any(|&index| index >= order.len()) {
+            return None;
+        }
+        // 孩子的下标必须大于父节点，除根节点外每个节点恰好被引用一次，深度不超过遍历栈的大小
+        let mut depths = vec![None; nodes.len()];
+        depths[0] = Some(0);
+        for (index, node) in nodes.iter().enumerate() {
+            let depth = depths[index]?;
+            let valid = match node.content {
+                BvhContent::Leaf { start, count } => {
+                    count > 0
+                        && start
+                            .checked_add(count)
+                            .is_some_and(|end| end <= order.len())
+                }
+                BvhContent::Interior { second, .. } => {
+                    index + 1 < second
+                        && second < nodes.len()
+                        && depth < STACK_SIZE
+                        && depths[index + 1].is_none()
+                        && depths[second].is_none()
+                }
+            };
+            if !valid {
+                return None;
+            }
+            if let BvhContent::Interior { second, .. } = node.content {
+                depths[index + 1] = Some(depth + 1);
+                depths[second] = Some(depth + 1);
+            }
+        }
+        Some(BvhLayout { nodes, order })
     }
 }
 
 impl BvhNode {
-    pub fn new(list: &mut [Arc<dyn Hittable>]) -> Self {
-        let axis = m_random_range(0..3);
-        list.sort_by(|a, b| {
-            let a_box = a.bounding_box();
-            let b_box = b.bounding_box();
-            a_box[axis].0.total_cmp(&b_box[axis].0)
-        });
+    pub fn new(list: &[Arc<dyn Hittable>], options: &BvhOptions) -> Self {
+        let boxes: Vec<AABB> = list
+            .iter()
+            .map(|item| item.bounding_box().clone())
+            .collect();
+        BvhNode::from_layout(BvhLayout::build(&boxes, options), list, options)
+    }
 
-        let left: Arc<dyn Hittable>;
-        let right: Arc<dyn Hittable>;
+    // layout 必须由与 list 一一对应的包围盒构建
+    pub fn from_layout(
+        layout: BvhLayout,
+        list: &[Arc<dyn Hittable>],
+        options: &BvhOptions,
+    ) -> Self {
+        assert_eq!(
+            layout.len(),
+            list.len(),
+            "BVH layout does not match primitives."
+        );
+        let mut bvh = BvhNode {
+            primitives: layout
+                .order
+                .iter()
+                .map(|&index| list[index].clone())
+                .collect(),
+            nodes: layout.nodes,
+            options: *options,
+            build_cost: 0.0,
+        };
+        bvh.build_cost = bvh.sah_cost();
+        bvh
+    }
+
+    // 按图元在快门区间 shutter 内的包围盒自底向上更新各节点，树的结构不变
+    pub fn refit(&mut self, shutter: Vec2) {
+        // 孩子的下标总是大于父节点，倒序遍历即可保证先更新孩子
+        for index in (0..self.nodes.len()).rev() {
+            let bbox = match self.nodes[index].content {
+                BvhContent::Leaf { start, count } => {
+                    hittable::bounding_box_during(&self.primitives[start..start + count], shutter)
+                }
+                BvhContent::Interior { second, .. } => {
+                    AABB::from_aabb(&self.nodes[index + 1].bbox, &self.nodes[second].bbox)
+                }
+            };
+            self.nodes[index].bbox = bbox;
+        }
+    }
+
+    // 整棵树的 SAH 代价，以根节点的表面积归一化
+    pub fn sah_cost(&self) -> f64 {
+        let cost: f64 = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let area = node.bbox.surface_area();
+                match node.content {
+                    BvhContent::Leaf { count, .. } => count as f64 * area,
+                    BvhContent::Interior { .. } => TRAVERSAL_COST * area,
+                }
+            })
+            .sum();
+        cost / self.nodes[0].bbox.surface_area()
+    }
+}
+
+impl Accelerator for BvhNode {
+    // 先 refit，质量下降超过阈值时才重新构建
+    fn update(&mut self, shutter: Vec2) -> bool {
+        self.refit(shutter);
+        if self.sah_cost() <= self.build_cost * self.options.rebuild_threshold {
+            return false;
+        }
+        let boxes: Vec<AABB> = self
+            .primitives
+            .iter()
+            .map(|item| item.bounding_box_during(shutter))
+            .collect();
+        let layout = BvhLayout::build(&boxes, &self.options);
+        *self = BvhNode::from_layout(layout, &self.primitives, &self.options);
+        true
+    }
+}
+
+struct Builder<'a> {
+    options: &'a BvhOptions,
+    nodes: Vec<LinearNode>,
+}
+
+impl Builder<'_> {
+    // 构建 list 对应的子树并追加到 nodes 末尾，start 为 list 在全部图元中的起始位置
+    fn build(&mut self, list: &mut [BuildItem], start: usize, depth: usize) {
         let len = list.len();
+        // 图元较多时把统计分给多个线程，越深的层已经有越多子树在并行构建
+        let threads = if len >= PARALLEL_THRESHOLD {
+            (self.options.threads >> depth).max(1)
+        } else {
+            1
+        };
+        let (bbox, centroids) = reduce_chunks(
+            list,
+            threads,
+            |chunk| {
+                let bbox = chunk
+                    .iter()
+                    .map(|item| item.bbox.clone())
+                    .reduce(|a, b| AABB::from_aabb(&a, &b))
+                    .unwrap();
+                let centroids = chunk.iter().map(|item| item.bbox.centroid()).fold(
+                    (
+                        Point3::from_single(f64::INFINITY),
+                        Point3::from_single(f64::NEG_INFINITY),
+                    ),
+                    |(lower, upper), c| (lower.min(c), upper.max(c)),
+                );
+                (bbox, centroids)
+            },
+            |a, b| {
+                (
+                    AABB::from_aabb(&a.0, &b.0),
+                    (a.1.0.min(b.1.0), a.1.1.max(b.1.1)),
+                )
+            },
+        );
+        let leaf = BvhContent::Leaf { start, count: len };
         if len == 1 {
-            left = list[0].clone();
-            right = list[0].clone();
-        } else if len == 2 {
-            left = list[0].clone();
-            right = list[1].clone();
+            self.nodes.push(LinearNode {
+                bbox,
+                content: leaf,
+            });
+            return;
+        }
+
+        let extent = centroids.1 - centroids.0;
+        let axis = if extent.0 > extent.1 && extent.0 > extent.2 {
+            0
+        } else if extent.1 > extent.2 {
+            1
         } else {
-            let (left_list, right_list) = list.split_at_mut(len / 2);
-            left = Arc::new(BvhNode::new(left_list));
-            right = Arc::new(BvhNode::new(right_list));
+            2
+        };
+        // 所有重心重合时无法按位置划分
+        if extent[axis] <= 0.0 {
+            if len <= self.options.max_leaf_size {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    content: leaf,
+                });
+                return;
+            }
+            return self.split(list, len / 2, start, depth, axis, bbox);
+        }
+        if depth >= MAX_SAH_DEPTH {
+            list.select_nth_unstable_by(len / 2, |a, b| {
+                let a = a.bbox.centroid()[axis];
+                a.total_cmp(&b.bbox.centroid()[axis])
+            });
+            return self.split(list, len / 2, start, depth, axis, bbox);
+        }
+        let bins = self.options.bins.max(2);
+        let bin_of = |item: &BuildItem| {
+            let c = item.bbox.centroid()[axis];
+            (((c - centroids.0[axis]) / extent[axis] * bins as f64) as usize).min(bins - 1)
+        };
+        let (counts, boxes) = reduce_chunks(
+            list,
+            threads,
+            |chunk| {
+                let mut counts = vec![0; bins];
+                let mut boxes: Vec<Option<AABB>> = vec![None; bins];
+                for item in chunk {
+                    let bin = bin_of(item);
+                    counts[bin] += 1;
+                    let item_box = &item.bbox;
+                    boxes[bin] = Some(match &boxes[bin] {
+                        Some(b) => AABB::from_aabb(b, item_box),
+                        None => item_box.clone(),
+                    });
+                }
+                (counts, boxes)
+            },
+            |(mut counts, mut boxes), (other_counts, other_boxes)| {
+                for (i, other) in other_boxes.into_iter().enumerate() {
+                    counts[i] += other_counts[i];
+                    boxes[i] = match (boxes[i].take(), other) {
+                        (Some(a), Some(b)) => Some(AABB::from_aabb(&a, &b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                (counts, boxes)
+            },
+        );
+
+        // 从两端分别累积，below[i] 为前 i + 1 个桶的面积与数量，above[i] 为第 i 个桶及之后的
+        let mut below = vec![(0.0, 0); bins];
+        let mut above = vec![(0.0, 0); bins];
+        for (order, result) in [
+            ((0..bins).collect::<Vec<_>>(), &mut below),
+            ((0..bins).rev().collect(), &mut above),
+        ] {
+            let mut acc: Option<AABB> = None;
+            let mut count = 0;
+            for i in order {
+                if let Some(b) = &boxes[i] {
+                    acc = Some(acc.map_or_else(|| b.clone(), |a| AABB::from_aabb(&a, b)));
+                }
+                count += counts[i];
+                result[i] = (acc.as_ref().map_or(0.0, AABB::surface_area), count);
+            }
+        }
+        let area = bbox.surface_area();
+        let (best, best_cost) = (0..bins - 1)
+            .map(|i| {
+                let (area_l, count_l) = below[i];
+                let (area_r, count_r) = above[i + 1];
+                let cost =
+                    TRAVERSAL_COST + (area_l * count_l as f64 + area_r * count_r as f64) / area;
+                (i, cost)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        if len <= self.options.max_leaf_size && best_cost >= len as f64 {
+            self.nodes.push(LinearNode {
+                bbox,
+                content: leaf,
+            });
+            return;
+        }
+
+        let mut middle = partition_in_place(list, |item| bin_of(item) <= best);
+        if middle == 0 || middle == len {
+            middle = len / 2;
+        }
+        self.split(list, middle, start, depth, axis, bbox);
+    }
+
+    fn split(
+        &mut self,
+        list: &mut [BuildItem],
+        middle: usize,
+        start: usize,
+        depth: usize,
+        axis: usize,
+        bbox: AABB,
+    ) {
+        let list_len = list.len();
+        let index = self.nodes.len();
+        self.nodes.push(LinearNode {
+            bbox,
+            content: BvhContent::Interior { second: 0, axis },
+        });
+        let (left, right) = list.split_at_mut(middle);
+        // 上面几层的两棵子树交给不同线程构建，右子树的节点下标再整体平移
+        if list_len >= PARALLEL_THRESHOLD && (1 << depth) < 2 * self.options.threads {
+            let options = self.options;
+            let second = thread::scope(|scope| {
+                let handle = scope.spawn(|| {
+                    let mut builder = Builder {
+                        options,
+                        nodes: Vec::with_capacity(2 * right.len()),
+                    };
+                    builder.build(right, start + middle, depth + 1);
+                    builder.nodes
+                });
+                self.build(left, start, depth + 1);
+                let second = self.nodes.len();
+                let nodes = handle.join().expect("BVH build thread panicked.");
+                self.nodes.extend(nodes.into_iter().map(|mut node| {
+                    if let BvhContent::Interior { second: child, .. } = &mut node.content {
+                        *child += second;
+                    }
+                    node
+                }));
+                second
+            });
+            self.nodes[index].content = BvhContent::Interior { second, axis };
+            return;
+        }
+        self.build(left, start, depth + 1);
+        let second = self.nodes.len();
+        self.build(right, start + middle, depth + 1);
+        self.nodes[index].content = BvhContent::Interior { second, axis };
+    }
+}
+
+// 把 list 分成 threads 块分别统计后合并
+fn reduce_chunks<T: Send>(
+    list: &[BuildItem],
+    threads: usize,
+    map: impl Fn(&[BuildItem]) -> T + Sync,
+    merge: impl Fn(T, T) -> T,
+) -> T {
+    if threads <= 1 {
+        return map(list);
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = list
+            .chunks(list.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(|| map(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("BVH build thread panicked."))
+            .reduce(merge)
+            .unwrap()
+    })
+}
+
+// 原地划分，满足条件的元素移到前面，返回它们的数量
+fn partition_in_place<T>(list: &mut [T], predicate: impl Fn(&T) -> bool) -> usize {
+    let mut first = 0;
+    for i in 0..list.len() {
+        if predicate(&list[i]) {
+            list.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color, geometry::Sphere, hittable::HittableList, material::Lambertian,
+        random::m_random_range, texture::SolidTexture,
+    };
+
+    fn random_spheres(count: usize, spread: f64) -> Vec<Arc<dyn Hittable>> {
+        let material = Arc::new(Lambertian::new(SolidTexture::new(Color::from_single(0.5))));
+        (0..count)
+            .map(|_| {
+                let center = Vec3::random_rage(-spread..spread);
+                let radius = m_random_range(0.1..1.5);
+                Arc::new(Sphere::new(center, center, radius, material.clone())) as Arc<dyn Hittable>
+            })
+            .collect()
+    }
+
+    fn contains(outer: &AABB, inner: &AABB) -> bool {
+        (0..3).all(|axis| outer[axis].0 <= inner[axis].0 && inner[axis].1 <= outer[axis].1)
+    }
+
+    // 从根节点遍历：每个节点恰好访问一次，父节点的包围盒包住孩子，
+    // 叶子不超过 max_leaf_size 个图元且所有叶子恰好覆盖每个图元一次
+    fn check_layout(bvh: &BvhNode, max_leaf_size: usize, shutter: Vec2) {
+        let mut visited = vec![false; bvh.nodes.len()];
+        let mut covered = vec![false; bvh.primitives.len()];
+        let mut stack = vec![(0, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            assert!(!visited[index], "Node {index} is referenced twice.");
+            visited[index] = true;
+            assert!(depth < STACK_SIZE);
+            let node = &bvh.nodes[index];
+            match node.content {
+                BvhContent::Leaf { start, count } => {
+                    assert!(
+                        count > 0 && count <= max_leaf_size,
+                        "Leaf has {count} primitives."
+                    );
+                    let range = start..start + count;
+                    for (covered, primitive) in covered[range.clone()]
+                        .iter_mut()
+                        .zip(&bvh.primitives[range])
+                    {
+                        assert!(!*covered, "A primitive is in two leaves.");
+                        *covered = true;
+                        assert!(contains(
+                            &node.bbox,
+                            &primitive.bounding_box_during(shutter)
+                        ));
+                    }
+                }
+                BvhContent::Interior { second, .. } => {
+                    assert!(index + 1 < second && second < bvh.nodes.len());
+                    for child in [index + 1, second] {
+                        assert!(contains(&node.bbox, &bvh.nodes[child].bbox));
+                        stack.push((child, depth + 1));
+                    }
+                }
+            }
         }
-        let bbox = AABB::from_aabb(left.bounding_box(), right.bounding_box());
-        BvhNode { bbox, left, right }
+        assert!(visited.iter().all(|&v| v), "Some nodes are unreachable.");
+        assert!(
+            covered.iter().all(|&c| c),
+            "Some primitives are in no leaf."
+        );
+    }
+
+    // 最近交点和遮挡结果必须与逐个求交一致
+    fn assert_matches(bvh: &impl Hittable, brute_force: &HittableList, ray: &Ray, t_range: Vec2) {
+        let expected = brute_force.hit(ray, t_range).map(|rec| rec.t);
+        let actual = bvh.hit(ray, t_range).map(|rec| rec.t);
+        match (expected, actual) {
+            (Some(e), Some(a)) => assert!((e - a).abs() < 1e-9, "{e} != {a}"),
+            _ => assert_eq!(expected, actual),
+        }
+        assert_eq!(
+            brute_force.occluded(ray, t_range),
+            bvh.occluded(ray, t_range)
+        );
+    }
+
+    fn brute_force(list: &[Arc<dyn Hittable>]) -> HittableList {
+        let mut result = HittableList::new();
+        for item in list {
+            result.push(item.clone());
+        }
+        result
+    }
+
+    #[test]
+    fn leaves_respect_max_leaf_size() {
+        let shutter = Vec2::new(0.0, 1.0);
+        for max_leaf_size in [1, 2, 4, 7] {
+            let options = BvhOptions {
+                max_leaf_size,
+                ..BvhOptions::default()
+            };
+            let list = random_spheres(500, 10.0);
+            check_layout(&BvhNode::new(&list, &options), max_leaf_size, shutter);
+            // 重心全部重合时也要继续划分
+            let same = vec![list[0].clone(); 50];
+            check_layout(&BvhNode::new(&same, &options), max_leaf_size, shutter);
+        }
+    }
+
+    #[test]
+    fn hits_match_brute_force() {
+        let list = random_spheres(200, 10.0);
+        let bvh = BvhNode::new(&list, &BvhOptions::default());
+        let brute_force = brute_force(&list);
+        for _ in 0..20000 {
+            let origin: Point3 = Vec3::random_rage(-15.0..15.0);
+            let ray = Ray::new(origin, Vec3::random_rage(-1.0..1.0), 0.0);
+            let t_range = Vec2::new(0.001, m_random_range(1.0..40.0));
+            assert_matches(&bvh, &brute_force, &ray, t_range);
+        }
+    }
+
+    #[test]
+    fn closest_hit_from_inside_overlapping_boxes() {
+        // 大球挤在一起，叶子和内部节点的包围盒大量重叠，光线起点在多个包围盒内部
+        let list = random_spheres(100, 3.0)
+            .into_iter()
+            .chain(random_spheres(20, 1.0))
+            .collect::<Vec<_>>();
+        let bvh = BvhNode::new(&list, &BvhOptions::default());
+        let brute_force = brute_force(&list);
+        let leaf_boxes: Vec<&AABB> = bvh
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.content, BvhContent::Leaf { .. }))
+            .map(|node| &node.bbox)
+            .collect();
+        let mut inside_overlap = 0;
+        for _ in 0..20000 {
+            let origin: Point3 = Vec3::random_rage(-3.0..3.0);
+            let point = AABB::new(origin, origin);
+            if leaf_boxes
+                .iter()
+                .filter(|bbox| contains(bbox, &point))
+                .count()
+                >= 2
+            {
+                inside_overlap += 1;
+            }
+            let ray = Ray::new(origin, Vec3::random_rage(-1.0..1.0), 0.0);
+            assert_matches(&bvh, &brute_force, &ray, Vec2::new(0.001, f64::INFINITY));
+        }
+        assert!(inside_overlap > 10000, "{inside_overlap}");
+    }
+
+    #[test]
+    fn parallel_build_matches_serial_build() {
+        let list = random_spheres(2 * PARALLEL_THRESHOLD + 1000, 50.0);
+        let serial_options = BvhOptions {
+            threads: 1,
+            ..BvhOptions::default()
+        };
+        let parallel_options = BvhOptions {
+            threads: 4,
+            ..serial_options
+        };
+        let serial = BvhNode::new(&list, &serial_options);
+        let parallel = BvhNode::new(&list, &parallel_options);
+        check_layout(
+            &parallel,
+            parallel_options.max_leaf_size,
+            Vec2::new(0.0, 1.0),
+        );
+
+        // 各线程的统计合并后与单线程完全相同，两棵树应当一致
+        assert_eq!(serial.nodes.len(), parallel.nodes.len());
+        for (a, b) in serial.nodes.iter().zip(&parallel.nodes) {
+            assert!(contains(&a.bbox, &b.bbox) && contains(&b.bbox, &a.bbox));
+            match (&a.content, &b.content) {
+                (BvhContent::Leaf { start, count }, BvhContent::Leaf { start: s, count: c }) => {
+                    assert_eq!((start, count), (s, c));
+                }
+                (
+                    BvhContent::Interior { second, axis },
+                    BvhContent::Interior { second: s, axis: x },
+                ) => assert_eq!((second, axis), (s, x)),
+                _ => panic!("Serial and parallel builds differ."),
+            }
+        }
+        assert!(
+            serial
+                .primitives
+                .iter()
+                .zip(&parallel.primitives)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+        );
+
+        for _ in 0..5000 {
+            let origin: Point3 = Vec3::random_rage(-60.0..60.0);
+            let ray = Ray::new(origin, Vec3::random_rage(-1.0..1.0), 0.0);
+            let t_range = Vec2::new(0.001, f64::INFINITY);
+            let expected = serial.hit(&ray, t_range).map(|rec| rec.t);
+            assert_eq!(parallel.hit(&ray, t_range).map(|rec| rec.t), expected);
+            assert_eq!(parallel.occluded(&ray, t_range), expected.is_some());
+        }
+    }
+
+    // 从 [-10, 10] 中的随机位置移动到 target(center) 的球
+    fn moving_spheres(count: usize, target: impl Fn(Point3) -> Point3) -> Vec<Arc<dyn Hittable>> {
+        let material = Arc::new(Lambertian::new(SolidTexture::new(Color::from_single(0.5))));
+        (0..count)
+            .map(|_| {
+                let center = Vec3::random_rage(-10.0..10.0);
+                let radius = m_random_range(0.1..0.5);
+                Arc::new(Sphere::new(
+                    center,
+                    target(center),
+                    radius,
+                    material.clone(),
+                )) as Arc<dyn Hittable>
+            })
+            .collect()
+    }
+
+    fn assert_matches_at(bvh: &BvhNode, list: &[Arc<dyn Hittable>], time: f64) {
+        let brute_force = brute_force(list);
+        for _ in 0..5000 {
+            let origin: Point3 = Vec3::random_rage(-15.0..15.0);
+            let ray = Ray::new(origin, Vec3::random_rage(-1.0..1.0), time);
+            assert_matches(bvh, &brute_force, &ray, Vec2::new(0.001, f64::INFINITY));
+        }
+    }
+
+    // 按起点位置构建，build_cost 对应时刻 0 的包围盒
+    fn build_at_start(list: &[Arc<dyn Hittable>], options: &BvhOptions) -> BvhNode {
+        let boxes: Vec<AABB> = list
+            .iter()
+            .map(|item| item.bounding_box_during(Vec2::new(0.0, 0.0)))
+            .collect();
+        BvhNode::from_layout(BvhLayout::build(&boxes, options), list, options)
+    }
+
+    #[test]
+    fn small_motion_refits_without_rebuild() {
+        let list = moving_spheres(500, |center| center + Vec3::random_rage(-0.05..0.05));
+        let options = BvhOptions::default();
+        let mut bvh = build_at_start(&list, &options);
+        let (nodes, cost) = (bvh.nodes.len(), bvh.build_cost);
+        // 移动到终点后只 refit，父节点的包围盒仍然包住孩子
+        let shutter = Vec2::new(1.0, 1.0);
+        assert!(!bvh.update(shutter));
+        assert_eq!((bvh.nodes.len(), bvh.build_cost), (nodes, cost));
+        check_layout(&bvh, options.max_leaf_size, shutter);
+        assert_matches_at(&bvh, &list, 1.0);
+    }
+
+    #[test]
+    fn large_sah_degradation_triggers_rebuild() {
+        // 每个球都移动到随机的新位置，原来的划分完全失效
+        let list = moving_spheres(500, |_| Vec3::random_rage(-10.0..10.0));
+        let options = BvhOptions::default();
+        let mut bvh = build_at_start(&list, &options);
+        let shutter = Vec2::new(1.0, 1.0);
+        let mut refitted = build_at_start(&list, &options);
+        refitted.refit(shutter);
+        assert!(refitted.sah_cost() > bvh.build_cost * options.rebuild_threshold);
+
+        assert!(bvh.update(shutter));
+        check_layout(&bvh, options.max_leaf_size, shutter);
+        assert!(bvh.build_cost < refitted.sah_cost());
+        assert_matches_at(&bvh, &list, 1.0);
     }
 }
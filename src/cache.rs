@@ -0,0 +1,322 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    bvh::{BvhLayout, BvhOptions},
+    color::Color,
+    mesh::MeshData,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// 文件格式变化时递增，旧的缓存会因为键不同而自然失效
+const FORMAT_VERSION: u64 = 1;
+const MAGIC: &[u8; 4] = b"RTMC";
+
+// 64 位 FNV-1a 哈希，依次处理各段数据
+pub fn fnv1a(chunks: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in chunks.iter().copied().flatten() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub struct CacheWriter {
+    bytes: Vec<u8>,
+}
+
+impl CacheWriter {
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+// 读取越界时返回 None，调用者把它当作缓存损坏处理
+pub struct CacheReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl CacheReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.bytes.split_first_chunk::<N>()?;
+        self.bytes = rest;
+        Some(*head)
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn usize(&mut self) -> Option<usize> {
+        self.u64()?.try_into().ok()
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    // 先读长度再逐个读取，长度明显超过剩余字节数时视为损坏
+    pub fn vec<T>(&mut self, mut read: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.usize()?;
+        if len > self.bytes.len() {
+            return None;
+        }
+        (0..len).map(|_| read(self)).collect()
+    }
+}
+
+fn write_vec3(writer: &mut CacheWriter, v: Vec3) {
+    writer.f64(v.0);
+    writer.f64(v.1);
+    writer.f64(v.2);
+}
+
+fn read_vec3(reader: &mut CacheReader) -> Option<Vec3> {
+    Some(Vec3::new(reader.f64()?, reader.f64()?, reader.f64()?))
+}
+
+// 网格缓冲和构建好的 BVH 结构
+pub struct CachedMesh {
+    pub data: MeshData,
+    pub face_materials: Vec<usize>,
+    pub layout: BvhLayout,
+}
+
+// 以源文件内容的哈希为文件名，把加载后的网格和 BVH 写入 dir
+pub struct MeshCache {
+    dir: PathBuf,
+}
+
+impl MeshCache {
+    pub fn new(dir: &str) -> Self {
+        fs::create_dir_all(dir).expect("Failed to create cache directory.");
+        MeshCache {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    // 读取并哈希源文件，同一文件中的多个网格共用返回的 SourceCache
+    pub fn source(&self, path: &str) -> SourceCache<'_> {
+        let source = fs::read(path).expect("Failed to open mesh file.");
+        SourceCache {
+            cache: self,
+            source_hash: fnv1a(&[&source]),
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.mesh"))
+    }
+
+    pub fn load(&self, key: u64) -> Option<CachedMesh> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        let mesh = read_mesh(&bytes, key);
+        if mesh.is_none() {
+            println!("缓存 {} 已损坏，将重新构建。", self.path(key).display());
+        }
+        mesh
+    }
+
+    // 写入失败只影响下次加载的速度，不中断渲染
+    pub fn store(&self, key: u64, data: &MeshData, face_materials: &[usize], layout: &BvhLayout) {
+        let bytes = write_mesh(key, data, face_materials, layout);
+        // 先写临时文件再改名，避免并行的渲染读到写了一半的缓存
+        let path = self.path(key);
+        let temp = path.with_extension("tmp");
+        if let Err(error) = fs::write(&temp, &bytes).and_then(|_| fs::rename(&temp, &path)) {
+            println!("无法写入缓存 {}：{error}", path.display());
+        }
+    }
+}
+
+fn write_mesh(key: u64, data: &MeshData, face_materials: &[usize], layout: &BvhLayout) -> Vec<u8> {
+    let mut writer = CacheWriter { bytes: vec![] };
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.u64(key);
+    writer.usize(data.positions.len());
+    data.positions
+        .iter()
+        .for_each(|&p| write_vec3(&mut writer, p));
+    writer.usize(data.normals.len());
+    data.normals
+        .iter()
+        .for_each(|&n| write_vec3(&mut writer, n));
+    writer.usize(data.uvs.len());
+    for uv in &data.uvs {
+        writer.f64(uv.0);
+        writer.f64(uv.1);
+    }
+    writer.usize(data.colors.len());
+    data.colors.iter().for_each(|&c| write_vec3(&mut writer, c));
+    writer.usize(data.indices.len());
+    data.indices.iter().flatten().for_each(|&i| writer.usize(i));
+    writer.usize(face_materials.len());
+    face_materials.iter().for_each(|&m| writer.usize(m));
+    layout.write(&mut writer);
+    writer.bytes
+}
+
+// 某个源文件对应的缓存，源文件的哈希只在创建时计算一次
+pub struct SourceCache<'a> {
+    cache: &'a MeshCache,
+    source_hash: u64,
+}
+
+impl SourceCache<'_> {
+    // 除了源文件，加载参数和 BVH 参数也会影响结果，一并计入键
+    pub fn key(&self, params: &str, options: &BvhOptions) -> u64 {
+        fnv1a(&[
+            &FORMAT_VERSION.to_le_bytes(),
+            &self.source_hash.to_le_bytes(),
+            params.as_bytes(),
+            &options.max_leaf_size.to_le_bytes(),
+            &options.bins.to_le_bytes(),
+        ])
+    }
+}
+
+// 加载网格并构建 BVH，有缓存时先按源文件和 params 的哈希查找
+pub fn load_mesh(
+    cache: Option<&SourceCache>,
+    params: &str,
+    options: &BvhOptions,
+    load: impl FnOnce() -> (MeshData, Vec<usize>),
+) -> CachedMesh {
+    let key = cache.map(|cache| cache.key(params, options));
+    if let Some(mesh) = cache
+        .zip(key)
+        .and_then(|(source, key)| source.cache.load(key))
+    {
+        return mesh;
+    }
+    let (data, face_materials) = load();
+    assert!(!data.indices.is_empty(), "Mesh has no triangles.");
+    let layout = BvhLayout::build(&data.face_boxes(), options);
+    if let Some((source, key)) = cache.zip(key) {
+        source.cache.store(key, &data, &face_materials, &layout);
+    }
+    CachedMesh {
+        data,
+        face_materials,
+        layout,
+    }
+}
+
+fn read_mesh(bytes: &[u8], key: u64) -> Option<CachedMesh> {
+    let mut reader = CacheReader { bytes };
+    if reader.take::<4>()? != *MAGIC || reader.u64()? != key {
+        return None;
+    }
+    let positions: Vec<Point3> = reader.vec(read_vec3)?;
+    let normals = reader.vec(read_vec3)?;
+    let uvs = reader.vec(|r| Some(Vec2::new(r.f64()?, r.f64()?)))?;
+    let colors: Vec<Color> = reader.vec(read_vec3)?;
+    let indices: Vec<[usize; 3]> = reader.vec(|r| Some([r.usize()?, r.usize()?, r.usize()?]))?;
+    let face_materials = reader.vec(CacheReader::usize)?;
+    let layout = BvhLayout::read(&mut reader)?;
+
+    let valid = indices.iter().flatten().all(|&i| i < positions.len())
+        && [normals.len(), uvs.len(), colors.len()]
+            .iter()
+            .all(|&len| len == 0 || len == positions.len())
+        && face_materials.len() == indices.len()
+        && layout.len() == indices.len()
+        && reader.bytes.is_empty();
+    if !valid {
+        return None;
+    }
+    let mut data = MeshData::new(positions, indices);
+    data.normals = normals;
+    data.uvs = uvs;
+    data.colors = colors;
+    Some(CachedMesh {
+        data,
+        face_materials,
+        layout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::m_random_range;
+
+    fn random_mesh() -> (MeshData, Vec<usize>, BvhLayout) {
+        let positions: Vec<Point3> = (0..50).map(|_| Vec3::random_rage(-1.0..1.0)).collect();
+        let indices: Vec<[usize; 3]> = (0..40)
+            .map(|_| [(); 3].map(|_| m_random_range(0..positions.len())))
+            .collect();
+        let normals = positions.iter().map(|p| p.normalize()).collect();
+        let uvs = positions.iter().map(|p| Vec2::new(p.0, p.1)).collect();
+        let face_materials = (0..indices.len()).map(|face| face % 3).collect();
+        let data = MeshData::new(positions, indices)
+            .with_normals(normals)
+            .with_uvs(uvs);
+        let layout = BvhLayout::build(&data.face_boxes(), &BvhOptions::default());
+        (data, face_materials, layout)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (data, face_materials, layout) = random_mesh();
+        let bytes = write_mesh(7, &data, &face_materials, &layout);
+        let mesh = read_mesh(&bytes, 7).expect("Cache should be readable.");
+        assert_eq!(mesh.face_materials, face_materials);
+        // 重新写出的字节与原来完全一致，说明各项数据都没有变化
+        let again = write_mesh(7, &mesh.data, &mesh.face_materials, &mesh.layout);
+        assert_eq!(again, bytes);
+    }
+
+    #[test]
+    fn rejects_corrupt_files() {
+        let (data, face_materials, layout) = random_mesh();
+        let bytes = write_mesh(7, &data, &face_materials, &layout);
+        assert!(read_mesh(&bytes, 8).is_none());
+        for len in 0..bytes.len() {
+            assert!(read_mesh(&bytes[..len], 7).is_none());
+        }
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(read_mesh(&extra, 7).is_none());
+        let mut magic = bytes.clone();
+        magic[0] ^= 1;
+        assert!(read_mesh(&magic, 7).is_none());
+        // 第一个三角形的第一个顶点下标改为越界的值，前面依次是键、坐标、法线、纹理坐标、颜色和下标数量
+        let mut index = bytes;
+        let offset = 4 + 8 * (1 + (1 + 3 * 50) + (1 + 3 * 50) + (1 + 2 * 50) + 1 + 1);
+        index[offset..offset + 8].copy_from_slice(&50u64.to_le_bytes());
+        assert!(read_mesh(&index, 7).is_none());
+    }
+
+    fn write_node(writer: &mut CacheWriter, interior: bool, a: usize, b: usize) {
+        (0..6).for_each(|_| writer.f64(0.0));
+        writer.u64(interior as u64);
+        writer.usize(a);
+        writer.usize(b);
+    }
+
+    #[test]
+    fn rejects_shared_bvh_nodes() {
+        // 节点 3 同时是节点 0 和节点 1 的孩子，下标和深度都合法
+        let mut writer = CacheWriter { bytes: vec![] };
+        writer.usize(4);
+        write_node(&mut writer, true, 3, 0);
+        write_node(&mut writer, true, 3, 0);
+        write_node(&mut writer, false, 0, 1);
+        write_node(&mut writer, false, 0, 1);
+        writer.usize(1);
+        writer.usize(0);
+        let mut reader = CacheReader {
+            bytes: &writer.bytes,
+        };
+        assert!(BvhLayout::read(&mut reader).is_none());
+    }
+}
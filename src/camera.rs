@@ -3,13 +3,12 @@ use std::io::stdout;
 
 use crate::color::{Color, write_color};
 use crate::hittable::Hittable;
-use crate::random::{m_random, random_in_disk};
+use crate::math::mix;
+use crate::medium::Atmosphere;
+use crate::random::{m_random, random_in_disk, random_vector_on_sphere};
 use crate::ray::Ray;
 use crate::vec::Vec2;
-use crate::{
-    hittable::HittableList,
-    vec::{Point3, Vec3},
-};
+use crate::vec::{Point3, Vec3};
 
 #[derive(Debug)]
 pub struct Camera {
@@ -23,10 +22,19 @@ pub struct Camera {
     samples_per_pixel: i32,
     max_ray_range: f64,
     max_depth: i32,
+    atmosphere: Option<Atmosphere>,
+    // 给出时只渲染此距离内的环境光遮蔽，不做路径追踪
+    ambient_occlusion: Option<f64>,
+    // 光线的时间在此区间内均匀分布
+    shutter: Vec2,
 }
 
 impl Camera {
-    pub fn render(&self, world: &HittableList) {
+    pub fn set_shutter(&mut self, shutter: Vec2) {
+        self.shutter = shutter;
+    }
+
+    pub fn render(&self, world: &dyn Hittable, path: &str) {
         let (width, height) = self.image_resolution;
         let mut buffer = image::ImageBuffer::new(width, height);
         for row in 0..height {
@@ -35,23 +43,38 @@ impl Camera {
             for col in 0..width {
                 let mut color = Color::zero();
                 for _ in 0..self.samples_per_pixel {
-                    color += self.calc_ray(&self.get_ray(row, col), world, 0);
+                    let ray = self.get_ray(row, col);
+                    color += match self.ambient_occlusion {
+                        Some(distance) => self.calc_occlusion(&ray, world, distance),
+                        None => self.calc_ray(&ray, world, 0),
+                    };
                 }
                 color = (color / self.samples_per_pixel as f64).sqrt();
                 let pixel = buffer.get_pixel_mut(col, row);
                 *pixel = image::Rgb(write_color(color));
             }
         }
-        buffer
-            .save("output.png")
-            .expect("Failed to save render result.");
+        buffer.save(path).expect("Failed to save render result.");
     }
 
-    fn calc_ray(&self, ray: &Ray, world: &HittableList, depth: i32) -> Vec3 {
+    fn calc_ray(&self, ray: &Ray, world: &dyn Hittable, depth: i32) -> Vec3 {
         if depth >= self.max_depth {
             return Vec3::zero();
         }
-        match world.hit(ray, Vec2::new(0.001, self.max_ray_range)) {
+        let hit = world.hit(ray, Vec2::new(0.001, self.max_ray_range));
+        if let Some(atmosphere) = &self.atmosphere {
+            let t_max = hit.as_ref().map_or(self.max_ray_range, |rec| rec.t);
+            if let Some(t) = atmosphere.sample_scattering(ray, Vec2::new(0.001, t_max)) {
+                let scatter_result = atmosphere.scatter(ray);
+                return scatter_result.attenuation
+                    * self.calc_ray(
+                        &Ray::new(ray.at(t), scatter_result.scattered, ray.time),
+                        world,
+                        depth + 1,
+                    );
+            }
+        }
+        match hit {
             Some(result) => match result.material.scatter(ray, &result) {
                 Some(scatter_result) => {
                     scatter_result.attenuation
@@ -68,6 +91,21 @@ impl Camera {
         }
     }
 
+    // 从第一个交点按余弦分布发出一条遮挡光线，没有被挡住时为白色
+    fn calc_occlusion(&self, ray: &Ray, world: &dyn Hittable, distance: f64) -> Color {
+        let Some(rec) = world.hit(ray, Vec2::new(0.001, self.max_ray_range)) else {
+            return Color::from_single(1.0);
+        };
+        let direction = rec.normal + random_vector_on_sphere(rec.normal);
+        let shadow_ray = Ray::new(rec.p, direction, ray.time);
+        let t_max = distance / direction.length();
+        if world.occluded(&shadow_ray, Vec2::new(0.001, t_max)) {
+            Color::zero()
+        } else {
+            Color::from_single(1.0)
+        }
+    }
+
     fn get_ray(&self, row: u32, col: u32) -> Ray {
         let Self {
             pixel_delta_uv,
@@ -89,11 +127,17 @@ impl Camera {
         Ray::new(
             ray_current,
             (pixel_current - ray_current).normalize(),
-            m_random::<f64>(),
+            mix(self.shutter.0, self.shutter.1, m_random::<f64>()),
         )
     }
 }
 
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct CameraBuilder {
     pub vfov: f64,
     pub look_from: Point3,
@@ -107,6 +151,8 @@ pub struct CameraBuilder {
     pub samples_per_pixel: i32,
     pub max_ray_range: f64,
     pub max_depth: i32,
+    pub atmosphere: Option<Atmosphere>,
+    pub ambient_occlusion: Option<f64>,
 }
 impl CameraBuilder {
     pub fn new() -> Self {
@@ -123,6 +169,8 @@ impl CameraBuilder {
             samples_per_pixel: 50,
             max_ray_range: 100.0,
             max_depth: 50,
+            atmosphere: None,
+            ambient_occlusion: None,
         }
     }
     pub fn look_from(mut self, look_from: Point3) -> Self {
@@ -173,6 +221,14 @@ impl CameraBuilder {
         self.max_depth = depth;
         self
     }
+    pub fn atmosphere(mut self, atmosphere: Atmosphere) -> Self {
+        self.atmosphere = Some(atmosphere);
+        self
+    }
+    pub fn ambient_occlusion(mut self, distance: f64) -> Self {
+        self.ambient_occlusion = Some(distance);
+        self
+    }
 
     pub fn build(self) -> Camera {
         let Self {
@@ -188,6 +244,8 @@ impl CameraBuilder {
             samples_per_pixel,
             max_ray_range,
             max_depth,
+            atmosphere,
+            ambient_occlusion,
         } = self;
         let image_height = (image_width as f64 / aspect_ratio).floor() as u32;
         let image_height = if image_height < 1 { 1 } else { image_height };
@@ -222,6 +280,9 @@ impl CameraBuilder {
             samples_per_pixel,
             max_ray_range,
             max_depth,
+            atmosphere,
+            ambient_occlusion,
+            shutter: Vec2::new(0.0, 1.0),
         }
     }
 }
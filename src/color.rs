@@ -1,3 +1,5 @@
+use std::f64::consts::TAU;
+
 use crate::vec::Vec3;
 
 pub type Color = Vec3;
@@ -11,6 +13,6 @@ pub fn write_color(color: Color) -> [u8; 3] {
 }
 
 pub fn palette(a: Vec3, b: Vec3, c: Vec3, d: Vec3, t: f64) -> Color {
-    let tmp = 6.283185 * (c * t + d);
+    let tmp = TAU * (c * t + d);
     a + b * Vec3::new(tmp.0.cos(), tmp.1.cos(), tmp.2.cos())
 }
@@ -1,13 +1,45 @@
 use serde::Deserialize;
 
-use crate::bvh::BvhNode;
+use crate::aabb::AABB;
+use crate::accelerator::AcceleratorKind;
+use crate::bvh::BvhOptions;
+use crate::cache::{MeshCache, load_mesh};
 use crate::camera::CameraBuilder;
 use crate::color::Color;
-use crate::geometry::{ConstantMedium, Cube, GeometryEnum, Quad, RotateY, Sphere, Translate};
-use crate::hittable::HittableList;
-use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, MaterialEnum, Metal};
-use crate::texture::{CheckerTexture, NoiseTexture, SolidTexture, TextureEnum};
+use crate::csg::{Csg, CsgOperation, SolidEnum};
+use crate::curve::{CurveData, CurveShape, Curves};
+use crate::geometry::{
+    ConstantMedium, Cube, Ellipse, EllipseShape, GeometryEnum, Group, Instance, Planar, Quad,
+    RotateY, Sphere, Transformed, Translate, Triangle,
+};
+use crate::grid::GridOptions;
+use crate::heightfield::{HeightMap, Heightfield};
+use crate::hittable::{Hittable, HittableList};
+use crate::kdtree::KdTreeOptions;
+use crate::loader::{
+    obj::{load_obj, load_obj_materials},
+    ply::load_ply,
+    stl::load_stl,
+    vox::{VoxModel, load_vox},
+};
+use crate::material::{
+    Dielectric, DiffuseLight, Isotropic, KajiyaKay, Lambertian, MaterialEnum, Metal, PhaseFunction,
+};
+use crate::matrix::Mat44;
+use crate::medium::{
+    Atmosphere, DensityEnum, HeterogeneousMedium, ProceduralDensity, TurbulenceDensity,
+    VoxelDensity,
+};
+use crate::mesh::{Mesh, MeshData};
+use crate::quadric::{Cone, Cylinder, Torus};
+use crate::sdf::{Sdf, SdfNode};
+use crate::texture::{
+    CheckerTexture, ImageTexture, NoiseTexture, SolidTexture, TextureEnum, VertexColorTexture,
+};
+use crate::transform::{AnimatedTransform, Keyframes, Quaternion, Transform};
 use crate::vec::{Point3, Vec2, Vec3};
+use crate::voxel::{VoxelData, VoxelGrid};
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
 
@@ -17,6 +49,20 @@ pub struct Config {
     #[serde(default)]
     pub camera: Option<CameraConfig>,
     #[serde(default)]
+    pub atmosphere: Option<AtmosphereConfig>,
+    #[serde(default)]
+    pub gltf: Option<GltfConfig>,
+    #[serde(default)]
+    pub bvh: Option<BvhConfig>,
+    #[serde(default)]
+    pub accelerator: Option<AcceleratorConfig>,
+    #[serde(default)]
+    pub animation: Option<AnimationConfig>,
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    #[serde(default)]
+    pub prototypes: Vec<PrototypeConfig>,
+    #[serde(default)]
     pub objects: Vec<GeometryConfig>,
 }
 
@@ -35,6 +81,70 @@ pub struct CameraConfig {
     pub max_depth: Option<i32>,
     pub max_ray_range: Option<f64>,
     pub background_color: Option<Color>,
+    // 环境光遮蔽的最大距离，给出时渲染遮蔽图而不是完整的光照
+    pub ambient_occlusion: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BvhConfig {
+    pub max_leaf_size: Option<usize>,
+    pub bins: Option<usize>,
+    pub threads: Option<usize>,
+    pub rebuild_threshold: Option<f64>,
+}
+
+// 顶层与组使用的加速结构，组可以单独指定；网格和毛发内部总是使用 BVH
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AcceleratorConfig {
+    // 未给出的参数使用 [bvh] 中的设置
+    Bvh {
+        max_leaf_size: Option<usize>,
+        bins: Option<usize>,
+    },
+    Grid {
+        density: Option<f64>,
+    },
+    KdTree {
+        intersect_cost: Option<f64>,
+        traversal_cost: Option<f64>,
+        empty_bonus: Option<f64>,
+        max_leaf_size: Option<usize>,
+        max_depth: Option<usize>,
+    },
+}
+
+// 网格与其 BVH 的缓存目录，源文件不变时直接读取缓存
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    pub dir: String,
+}
+
+// 渲染图像序列，第 i 帧的快门区间为 [i / frames, (i + 1) / frames)
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationConfig {
+    pub frames: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GltfConfig {
+    pub path: String,
+    // 是否使用 glTF 文件中的相机，[camera] 中的设置仍会覆盖它
+    pub use_camera: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AtmosphereConfig {
+    pub density: f64,
+    pub albedo: Option<Color>,
+    pub height_falloff: Option<f64>,
+    pub base_height: Option<f64>,
+    pub phase: Option<PhaseConfig>,
 }
 
 pub fn load_config_from_file(path: &str) -> Config {
@@ -93,6 +203,9 @@ impl Configurable for CameraBuilder {
         if let Some(bg) = config.background_color {
             self = self.background_color(bg);
         }
+        if let Some(distance) = config.ambient_occlusion {
+            self = self.ambient_occlusion(distance);
+        }
         self
     }
 }
@@ -109,6 +222,12 @@ pub enum TextureConfig {
         color2: Option<Color>,
     },
     Noise {},
+    Image {
+        filename: String,
+    },
+    VertexColor {
+        fallback: Option<Color>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -131,6 +250,161 @@ pub enum MaterialConfig {
     Isotropic {
         texture: Option<TextureConfig>,
     },
+    KajiyaKay {
+        texture: Option<TextureConfig>,
+        specular: Option<Color>,
+        exponent: Option<f64>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PhaseConfig {
+    Isotropic {},
+    HenyeyGreenstein {
+        g: f64,
+        g2: Option<f64>,
+        weight: Option<f64>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DensityConfig {
+    Constant {
+        density: f64,
+    },
+    Turbulence {
+        density: f64,
+        scale: Option<f64>,
+        depth: Option<usize>,
+    },
+    VoxelGrid {
+        resolution: [usize; 3],
+        a: Point3,
+        b: Point3,
+        data: Option<Vec<f64>>,
+        path: Option<String>,
+    },
+    SphereFalloff {
+        center: Point3,
+        radius: f64,
+        density: f64,
+        falloff: Option<f64>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SdfConfig {
+    Sphere {
+        center: Point3,
+        radius: f64,
+    },
+    Box {
+        center: Point3,
+        half_extent: Vec3,
+    },
+    RoundBox {
+        center: Point3,
+        half_extent: Vec3,
+        radius: f64,
+    },
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: Point3,
+        b: Point3,
+        radius: f64,
+    },
+    Union {
+        nodes: Vec<SdfConfig>,
+    },
+    SmoothUnion {
+        nodes: Vec<SdfConfig>,
+        k: f64,
+    },
+    Subtraction {
+        base: Box<SdfConfig>,
+        cut: Box<SdfConfig>,
+        k: Option<f64>,
+    },
+    Repeat {
+        node: Box<SdfConfig>,
+        period: Vec3,
+    },
+    Twist {
+        node: Box<SdfConfig>,
+        rate: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CurveConfig {
+    points: [Point3; 4],
+    width: Vec2,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeightSourceConfig {
+    Image { path: String },
+    Noise { frequency: f64, octaves: usize },
+}
+
+// time 与光线的时间一致，取值在 [0, 1)
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeConfig<T> {
+    time: f64,
+    value: T,
+}
+
+// 静态值与关键帧至少给出一个，都缺失时以 missing 为提示信息 panic
+fn build_keyframes<T, U: Copy>(
+    value: Option<T>,
+    target: Option<T>,
+    keyframes: Option<Vec<KeyframeConfig<T>>>,
+    missing: &str,
+    build: impl Fn(T) -> U,
+) -> Keyframes<U> {
+    match (keyframes, value, target) {
+        (Some(keyframes), _, _) => Keyframes::new(
+            keyframes
+                .into_iter()
+                .map(|key| (key.time, build(key.value)))
+                .collect(),
+        ),
+        (None, Some(value), Some(target)) => {
+            Keyframes::new(vec![(0.0, build(value)), (1.0, build(target))])
+        }
+        (None, Some(value), None) => Keyframes::constant(build(value)),
+        (None, None, _) => panic!("{}", missing),
+    }
+}
+
+// 原型只构建一次，通过 instance 节点按名字引用
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrototypeConfig {
+    pub name: String,
+    pub geometry: GeometryConfig,
+}
+
+// 变换列表按顺序作用于物体，即第一个变换最先应用
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformConfig {
+    Translate { offset: Vec3 },
+    Scale { factor: Vec3 },
+    Rotate { axis: Vec3, angle: f64 },
+    Euler { angles: Vec3 },
+    Quaternion { w: f64, x: f64, y: f64, z: f64 },
+    Matrix { rows: [[f64; 4]; 4] },
 }
 
 #[derive(Deserialize)]
@@ -148,23 +422,167 @@ pub enum GeometryConfig {
         v: Vec3,
         material: Option<MaterialConfig>,
     },
+    // 椭圆以 center 为中心，u、v 为两个半轴，inner_ratio 为椭圆环内外半径之比
+    Ellipse {
+        center: Point3,
+        u: Vec3,
+        v: Vec3,
+        inner_ratio: Option<f64>,
+        material: Option<MaterialConfig>,
+    },
     Cube {
         a: Point3,
         b: Point3,
         material: Option<MaterialConfig>,
     },
+    Triangle {
+        a: Point3,
+        b: Point3,
+        c: Point3,
+        material: Option<MaterialConfig>,
+    },
+    Disk {
+        center: Point3,
+        normal: Option<Vec3>,
+        radius: f64,
+        inner_radius: Option<f64>,
+        material: Option<MaterialConfig>,
+    },
+    // 圆柱、圆锥以 center 为底面中心沿 +Y 放置，其他朝向用 transform 节点
+    Cylinder {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: Option<bool>,
+        material: Option<MaterialConfig>,
+    },
+    Cone {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: Option<bool>,
+        material: Option<MaterialConfig>,
+    },
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Option<MaterialConfig>,
+    },
+    Mesh {
+        vertices: Vec<Point3>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<Vec2>>,
+        material: Option<MaterialConfig>,
+    },
+    Obj {
+        path: String,
+        material: Option<MaterialConfig>,
+    },
+    Ply {
+        path: String,
+        material: Option<MaterialConfig>,
+    },
+    Stl {
+        path: String,
+        smoothing_angle: Option<f64>,
+        material: Option<MaterialConfig>,
+    },
+    // 每条曲线为 4 个控制点，width 为两端的宽度
+    Curves {
+        curves: Vec<CurveConfig>,
+        shape: Option<CurveShape>,
+        segments: Option<usize>,
+        material: Option<MaterialConfig>,
+    },
+    // MagicaVoxel 模型，没有指定材质时使用调色板颜色
+    Vox {
+        path: String,
+        origin: Point3,
+        voxel_size: Option<f64>,
+        material: Option<MaterialConfig>,
+    },
+    // voxels 中每项为 [x, y, z, k]，k 从 1 开始，对应 materials[k - 1]
+    Voxels {
+        size: [usize; 3],
+        voxels: Vec<[usize; 4]>,
+        materials: Vec<MaterialConfig>,
+        origin: Point3,
+        voxel_size: Option<f64>,
+    },
+    // 运动模糊：target_* 为快门结束时的值，keyframes 给出时忽略这两项
     Translate {
         instance: Box<GeometryConfig>,
-        offset: Vec3,
+        offset: Option<Vec3>,
+        target_offset: Option<Vec3>,
+        keyframes: Option<Vec<KeyframeConfig<Vec3>>>,
     },
     RotateY {
         instance: Box<GeometryConfig>,
-        angle: f64,
+        angle: Option<f64>,
+        target_angle: Option<f64>,
+        keyframes: Option<Vec<KeyframeConfig<f64>>>,
+    },
+    Transform {
+        instance: Box<GeometryConfig>,
+        #[serde(default)]
+        transform: Vec<TransformConfig>,
+        target_transform: Option<Vec<TransformConfig>>,
+        keyframes: Option<Vec<KeyframeConfig<Vec<TransformConfig>>>>,
+    },
+    // name 只用于报错信息
+    Group {
+        name: Option<String>,
+        children: Vec<GeometryConfig>,
+        #[serde(default)]
+        transform: Vec<TransformConfig>,
+        accelerator: Option<AcceleratorConfig>,
+    },
+    // 距离场需要显式给出包围盒 [min, max]
+    Sdf {
+        root: SdfConfig,
+        min: Point3,
+        max: Point3,
+        max_steps: Option<usize>,
+        step_scale: Option<f64>,
+        material: Option<MaterialConfig>,
+    },
+    // 高度场以 origin 为最小角，extent 为 XZ 平面上的尺寸，高度为 [0, height_scale]
+    Heightfield {
+        source: HeightSourceConfig,
+        resolution: Option<[usize; 2]>,
+        origin: Point3,
+        extent: Vec2,
+        height_scale: f64,
+        material: Option<MaterialConfig>,
+    },
+    // 布尔运算的两个操作数只能是 sphere、cube、transform 或 csg
+    Csg {
+        operation: CsgOperation,
+        left: Box<GeometryConfig>,
+        right: Box<GeometryConfig>,
+    },
+    Instance {
+        prototype: String,
+        #[serde(default)]
+        transform: Vec<TransformConfig>,
     },
     ConstantMedium {
         boundary: Box<GeometryConfig>,
         density: f64,
         texture: TextureConfig,
+        phase: Option<PhaseConfig>,
+    },
+    HeterogeneousMedium {
+        boundary: Box<GeometryConfig>,
+        density: DensityConfig,
+        sigma_a: Option<f64>,
+        sigma_s: Option<f64>,
+        texture: Option<TextureConfig>,
+        emission: Option<Color>,
+        emission_strength: Option<f64>,
+        phase: Option<PhaseConfig>,
     },
 }
 
@@ -183,13 +601,18 @@ fn build_texture(config: TextureConfig) -> TextureEnum {
             color2.unwrap_or(Color::one()),
         )),
         TextureConfig::Noise {} => TextureEnum::NoiseTexture(NoiseTexture::new()),
+        TextureConfig::Image { filename } => {
+            TextureEnum::ImageTexture(ImageTexture::new(&filename))
+        }
+        TextureConfig::VertexColor { fallback } => TextureEnum::VertexColorTexture(
+            VertexColorTexture::new(fallback.unwrap_or(Color::from_single(0.8))),
+        ),
     }
 }
 
 fn build_material(config: MaterialConfig) -> Arc<MaterialEnum> {
-    let texture_helper = |texture: Option<TextureConfig>| {
-        texture.map_or_else(|| TextureEnum::default(), |t| build_texture(t))
-    };
+    let texture_helper =
+        |texture: Option<TextureConfig>| texture.map_or_else(TextureEnum::default, build_texture);
     match config {
         MaterialConfig::Lambertian { texture } => Arc::new(MaterialEnum::Lambertian(
             Lambertian::new(texture_helper(texture)),
@@ -207,12 +630,231 @@ fn build_material(config: MaterialConfig) -> Arc<MaterialEnum> {
         MaterialConfig::Isotropic { texture } => Arc::new(MaterialEnum::Isotropic(Isotropic::new(
             texture_helper(texture),
         ))),
+        MaterialConfig::KajiyaKay {
+            texture,
+            specular,
+            exponent,
+        } => Arc::new(MaterialEnum::KajiyaKay(KajiyaKay::new(
+            texture_helper(texture),
+            specular.unwrap_or(Color::from_single(0.3)),
+            exponent.unwrap_or(40.0),
+        ))),
+    }
+}
+
+fn build_phase(config: Option<PhaseConfig>) -> PhaseFunction {
+    match config {
+        None | Some(PhaseConfig::Isotropic {}) => PhaseFunction::Isotropic,
+        Some(PhaseConfig::HenyeyGreenstein {
+            g,
+            g2: None,
+            weight,
+        }) => {
+            if weight.is_some() {
+                panic!("henyey_greenstein 的 weight 需要与 g2 一起使用！");
+            }
+            PhaseFunction::HenyeyGreenstein { g }
+        }
+        Some(PhaseConfig::HenyeyGreenstein {
+            g,
+            g2: Some(g2),
+            weight,
+        }) => PhaseFunction::TwoLobe {
+            g1: g,
+            g2,
+            weight: weight.unwrap_or(0.5),
+        },
+    }
+}
+
+pub fn build_atmosphere(config: AtmosphereConfig) -> Atmosphere {
+    Atmosphere::new(
+        config.density,
+        config.albedo.unwrap_or(Color::one()),
+        config.height_falloff.unwrap_or(0.0),
+        config.base_height.unwrap_or(0.0),
+        build_phase(config.phase),
+    )
+}
+
+fn build_density(config: DensityConfig) -> DensityEnum {
+    match config {
+        DensityConfig::Constant { density } => DensityEnum::Constant(density),
+        DensityConfig::Turbulence {
+            density,
+            scale,
+            depth,
+        } => DensityEnum::Turbulence(TurbulenceDensity::new(
+            density,
+            scale.unwrap_or(1.0),
+            depth.unwrap_or(7),
+        )),
+        DensityConfig::VoxelGrid {
+            resolution,
+            a,
+            b,
+            data,
+            path,
+        } => DensityEnum::VoxelGrid(match (data, path) {
+            (Some(data), _) => VoxelDensity::new(resolution, data, a, b),
+            (None, Some(path)) => VoxelDensity::from_file(&path, resolution, a, b),
+            (None, None) => panic!("voxel_grid 需要提供 data 或 path！"),
+        }),
+        DensityConfig::SphereFalloff {
+            center,
+            radius,
+            density,
+            falloff,
+        } => DensityEnum::Procedural(ProceduralDensity::sphere_falloff(
+            center,
+            radius,
+            density,
+            falloff.unwrap_or(1.0),
+        )),
+    }
+}
+
+fn build_sdf(config: SdfConfig) -> SdfNode {
+    let fold = |nodes: Vec<SdfConfig>, combine: &dyn Fn(SdfNode, SdfNode) -> SdfNode| {
+        nodes
+            .into_iter()
+            .map(build_sdf)
+            .reduce(combine)
+            .expect("SDF 的 union 和 smooth_union 至少需要一个节点！")
+    };
+    match config {
+        SdfConfig::Sphere { center, radius } => SdfNode::Sphere { center, radius },
+        SdfConfig::Box {
+            center,
+            half_extent,
+        } => SdfNode::Box {
+            center,
+            half_extent,
+        },
+        SdfConfig::RoundBox {
+            center,
+            half_extent,
+            radius,
+        } => SdfNode::RoundBox {
+            center,
+            half_extent,
+            radius,
+        },
+        SdfConfig::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        } => SdfNode::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        },
+        SdfConfig::Capsule { a, b, radius } => SdfNode::Capsule { a, b, radius },
+        SdfConfig::Union { nodes } => fold(nodes, &|a, b| SdfNode::Union(Box::new(a), Box::new(b))),
+        SdfConfig::SmoothUnion { nodes, k } => fold(nodes, &|a, b| {
+            SdfNode::SmoothUnion(Box::new(a), Box::new(b), k)
+        }),
+        SdfConfig::Subtraction { base, cut, k } => SdfNode::Subtraction(
+            Box::new(build_sdf(*base)),
+            Box::new(build_sdf(*cut)),
+            k.unwrap_or(0.0),
+        ),
+        SdfConfig::Repeat { node, period } => SdfNode::Repeat(Box::new(build_sdf(*node)), period),
+        SdfConfig::Twist { node, rate } => SdfNode::Twist(Box::new(build_sdf(*node)), rate),
+    }
+}
+
+fn build_animated_transform(
+    transform: Vec<TransformConfig>,
+    target: Option<Vec<TransformConfig>>,
+    keyframes: Option<Vec<KeyframeConfig<Vec<TransformConfig>>>>,
+) -> AnimatedTransform {
+    AnimatedTransform::new(build_keyframes(
+        Some(transform),
+        target,
+        keyframes,
+        "transform 需要提供 transform 或 keyframes！",
+        build_transform,
+    ))
+}
+
+fn build_transform(config: Vec<TransformConfig>) -> Transform {
+    config
+        .into_iter()
+        .map(|item| match item {
+            TransformConfig::Translate { offset } => Transform::translate(offset),
+            TransformConfig::Scale { factor } => Transform::scale(factor),
+            TransformConfig::Rotate { axis, angle } => Transform::rotate(axis, angle),
+            TransformConfig::Euler { angles } => Transform::euler(angles),
+            TransformConfig::Quaternion { w, x, y, z } => {
+                Transform::from_quaternion(Quaternion::new(w, x, y, z))
+            }
+            TransformConfig::Matrix { rows } => Transform::try_new(Mat44::new(rows))
+                .unwrap_or_else(|| panic!("matrix 变换 {rows:?} 不可逆！")),
+        })
+        .fold(Transform::identity(), |acc, item| item * acc)
+}
+
+pub fn build_bvh_options(config: Option<&BvhConfig>) -> BvhOptions {
+    let default = BvhOptions::default();
+    config.map_or(default, |config| BvhOptions {
+        max_leaf_size: config.max_leaf_size.unwrap_or(default.max_leaf_size),
+        bins: config.bins.unwrap_or(default.bins),
+        threads: config.threads.unwrap_or(default.threads),
+        rebuild_threshold: config
+            .rebuild_threshold
+            .unwrap_or(default.rebuild_threshold),
+    })
+}
+
+pub fn build_accelerator(config: Option<&AcceleratorConfig>, bvh: &BvhOptions) -> AcceleratorKind {
+    match config {
+        None => AcceleratorKind::Bvh(*bvh),
+        Some(AcceleratorConfig::Bvh {
+            max_leaf_size,
+            bins,
+        }) => AcceleratorKind::Bvh(BvhOptions {
+            max_leaf_size: max_leaf_size.unwrap_or(bvh.max_leaf_size),
+            bins: bins.unwrap_or(bvh.bins),
+            ..*bvh
+        }),
+        Some(AcceleratorConfig::Grid { density }) => {
+            let default = GridOptions::default();
+            AcceleratorKind::Grid(GridOptions {
+                density: density.unwrap_or(default.density),
+            })
+        }
+        Some(AcceleratorConfig::KdTree {
+            intersect_cost,
+            traversal_cost,
+            empty_bonus,
+            max_leaf_size,
+            max_depth,
+        }) => {
+            let default = KdTreeOptions::default();
+            AcceleratorKind::KdTree(KdTreeOptions {
+                intersect_cost: intersect_cost.unwrap_or(default.intersect_cost),
+                traversal_cost: traversal_cost.unwrap_or(default.traversal_cost),
+                empty_bonus: empty_bonus.unwrap_or(default.empty_bonus),
+                max_leaf_size: max_leaf_size.unwrap_or(default.max_leaf_size),
+                max_depth: max_depth.or(default.max_depth),
+            })
+        }
     }
 }
 
-fn build_geometry(config: GeometryConfig) -> GeometryEnum {
+// 构建几何体时需要共享的状态
+struct BuildContext {
+    prototypes: HashMap<String, Arc<GeometryEnum>>,
+    bvh: BvhOptions,
+    // 没有单独指定加速结构的组使用场景的设置
+    accelerator: AcceleratorKind,
+    cache: Option<MeshCache>,
+}
+
+fn build_geometry(config: GeometryConfig, context: &BuildContext) -> GeometryEnum {
     let material_helper = |material: Option<MaterialConfig>| {
-        material.map_or_else(|| Arc::new(MaterialEnum::default()), |m| build_material(m))
+        material.map_or_else(|| Arc::new(MaterialEnum::default()), build_material)
     };
     match config {
         GeometryConfig::Sphere {
@@ -229,33 +871,428 @@ fn build_geometry(config: GeometryConfig) -> GeometryEnum {
         GeometryConfig::Quad { q, u, v, material } => {
             GeometryEnum::Quad(Quad::new(q, u, v, material_helper(material)))
         }
+        GeometryConfig::Ellipse {
+            center,
+            u,
+            v,
+            inner_ratio,
+            material,
+        } => GeometryEnum::Ellipse(Planar::with_shape(
+            center,
+            u,
+            v,
+            EllipseShape {
+                inner_ratio: inner_ratio.unwrap_or(0.0),
+            },
+            material_helper(material),
+        )),
         GeometryConfig::Cube { a, b, material } => {
             GeometryEnum::Cube(Cube::new(a, b, material_helper(material)))
         }
-        GeometryConfig::Translate { instance, offset } => {
-            GeometryEnum::Translate(Translate::new(build_geometry(*instance), offset))
+        GeometryConfig::Triangle { a, b, c, material } => {
+            GeometryEnum::Triangle(Triangle::new(a, b, c, material_helper(material)))
+        }
+        GeometryConfig::Disk {
+            center,
+            normal,
+            radius,
+            inner_radius,
+            material,
+        } => GeometryEnum::Ellipse(Ellipse::disk(
+            center,
+            normal.unwrap_or(Vec3::from_axis_y(1.0)),
+            radius,
+            inner_radius.unwrap_or(0.0),
+            material_helper(material),
+        )),
+        GeometryConfig::Cylinder {
+            center,
+            radius,
+            height,
+            capped,
+            material,
+        } => GeometryEnum::Cylinder(Cylinder::new(
+            center,
+            radius,
+            height,
+            capped.unwrap_or(true),
+            material_helper(material),
+        )),
+        GeometryConfig::Cone {
+            center,
+            radius,
+            height,
+            capped,
+            material,
+        } => GeometryEnum::Cone(Cone::new(
+            center,
+            radius,
+            height,
+            capped.unwrap_or(true),
+            material_helper(material),
+        )),
+        GeometryConfig::Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        } => GeometryEnum::Torus(Torus::new(
+            center,
+            major_radius,
+            minor_radius,
+            material_helper(material),
+        )),
+        GeometryConfig::Mesh {
+            vertices,
+            indices,
+            normals,
+            uvs,
+            material,
+        } => {
+            let mut data = MeshData::new(vertices, indices);
+            if let Some(normals) = normals {
+                data = data.with_normals(normals);
+            }
+            if let Some(uvs) = uvs {
+                data = data.with_uvs(uvs);
+            }
+            GeometryEnum::Mesh(Mesh::new(data, material_helper(material), &context.bvh))
+        }
+        GeometryConfig::Obj { path, material } => {
+            let mut obj_materials = None;
+            let source = context.cache.as_ref().map(|cache| cache.source(&path));
+            let mesh = load_mesh(source.as_ref(), "obj", &context.bvh, || {
+                let model = load_obj(&path);
+                obj_materials = Some(model.materials);
+                (model.mesh, model.face_materials)
+            });
+            // 配置中给出的材质覆盖 .mtl 中的所有材质
+            let (materials, face_materials) = match material {
+                Some(material) => (
+                    vec![build_material(material)],
+                    vec![0; mesh.face_materials.len()],
+                ),
+                // 从缓存加载时只需重新读取材质
+                None => (
+                    obj_materials.unwrap_or_else(|| load_obj_materials(&path)),
+                    mesh.face_materials,
+                ),
+            };
+            GeometryEnum::Mesh(Mesh::with_layout(
+                mesh.data,
+                materials,
+                face_materials,
+                mesh.layout,
+                &context.bvh,
+            ))
+        }
+        GeometryConfig::Ply { path, material } => {
+            let source = context.cache.as_ref().map(|cache| cache.source(&path));
+            let mesh = load_mesh(source.as_ref(), "ply", &context.bvh, || {
+                let data = load_ply(&path);
+                let face_count = data.indices.len();
+                (data, vec![0; face_count])
+            });
+            // 没有指定材质时，带顶点颜色的模型默认用顶点颜色作为漫反射颜色
+            let material = match material {
+                None if !mesh.data.colors.is_empty() => Arc::new(MaterialEnum::Lambertian(
+                    Lambertian::new(TextureEnum::VertexColorTexture(VertexColorTexture::new(
+                        Color::from_single(0.8),
+                    ))),
+                )),
+                material => material_helper(material),
+            };
+            GeometryEnum::Mesh(Mesh::with_layout(
+                mesh.data,
+                vec![material],
+                mesh.face_materials,
+                mesh.layout,
+                &context.bvh,
+            ))
+        }
+        GeometryConfig::Stl {
+            path,
+            smoothing_angle,
+            material,
+        } => {
+            let params = format!("stl {smoothing_angle:?}");
+            let source = context.cache.as_ref().map(|cache| cache.source(&path));
+            let mesh = load_mesh(source.as_ref(), &params, &context.bvh, || {
+                let data = load_stl(&path, smoothing_angle);
+                let face_count = data.indices.len();
+                (data, vec![0; face_count])
+            });
+            GeometryEnum::Mesh(Mesh::with_layout(
+                mesh.data,
+                vec![material_helper(material)],
+                mesh.face_materials,
+                mesh.layout,
+                &context.bvh,
+            ))
+        }
+        GeometryConfig::Curves {
+            curves,
+            shape,
+            segments,
+            material,
+        } => GeometryEnum::Curves(Curves::new(
+            curves
+                .into_iter()
+                .map(|curve| CurveData {
+                    points: curve.points,
+                    width: curve.width,
+                })
+                .collect(),
+            shape.unwrap_or(CurveShape::Tube),
+            segments.unwrap_or(1),
+            material_helper(material),
+            &context.bvh,
+        )),
+        GeometryConfig::Vox {
+            path,
+            origin,
+            voxel_size,
+            material,
+        } => {
+            let VoxModel {
+                mut data,
+                materials,
+            } = load_vox(&path);
+            let materials = match material {
+                // 指定材质时所有体素共用这一种材质
+                Some(material) => {
+                    data.voxels.iter_mut().for_each(|v| *v = (*v).min(1));
+                    vec![build_material(material)]
+                }
+                None => materials,
+            };
+            GeometryEnum::Voxel(VoxelGrid::new(
+                data,
+                origin,
+                voxel_size.unwrap_or(1.0),
+                materials,
+            ))
+        }
+        GeometryConfig::Voxels {
+            size,
+            voxels,
+            materials,
+            origin,
+            voxel_size,
+        } => {
+            let mut data = VoxelData::new(size);
+            for [x, y, z, material] in voxels {
+                let material = u16::try_from(material).unwrap_or_else(|_| {
+                    panic!("体素 [{x}, {y}, {z}] 的材质编号 {material} 过大！")
+                });
+                data.set([x, y, z], material);
+            }
+            GeometryEnum::Voxel(VoxelGrid::new(
+                data,
+                origin,
+                voxel_size.unwrap_or(1.0),
+                materials.into_iter().map(build_material).collect(),
+            ))
+        }
+        GeometryConfig::Translate {
+            instance,
+            offset,
+            target_offset,
+            keyframes,
+        } => GeometryEnum::Translate(Translate::with_keyframes(
+            build_geometry(*instance, context),
+            build_keyframes(
+                offset,
+                target_offset,
+                keyframes,
+                "translate 需要提供 offset 或 keyframes！",
+                |offset| offset,
+            ),
+        )),
+        GeometryConfig::RotateY {
+            instance,
+            angle,
+            target_angle,
+            keyframes,
+        } => GeometryEnum::RotateY(RotateY::with_keyframes(
+            build_geometry(*instance, context),
+            build_keyframes(
+                angle,
+                target_angle,
+                keyframes,
+                "rotate_y 需要提供 angle 或 keyframes！",
+                |angle| angle,
+            ),
+        )),
+        GeometryConfig::Transform {
+            instance,
+            transform,
+            target_transform,
+            keyframes,
+        } => GeometryEnum::Transform(Transformed::animated(
+            build_geometry(*instance, context),
+            build_animated_transform(transform, target_transform, keyframes),
+        )),
+        GeometryConfig::Group {
+            name,
+            children,
+            transform,
+            accelerator,
+        } => {
+            let children = children
+                .into_iter()
+                .map(|child| Arc::new(build_geometry(child, context)) as Arc<dyn Hittable>)
+                .collect();
+            let kind = accelerator.map_or(context.accelerator, |config| {
+                build_accelerator(Some(&config), &context.bvh)
+            });
+            let group = GeometryEnum::Group(Group::new(name, children, &kind));
+            if transform.is_empty() {
+                group
+            } else {
+                GeometryEnum::Transform(Transformed::new(group, build_transform(transform)))
+            }
+        }
+        GeometryConfig::Sdf {
+            root,
+            min,
+            max,
+            max_steps,
+            step_scale,
+            material,
+        } => GeometryEnum::Sdf(Sdf::new(
+            build_sdf(root),
+            AABB::new(min, max),
+            max_steps.unwrap_or(256),
+            step_scale.unwrap_or(1.0),
+            material_helper(material),
+        )),
+        GeometryConfig::Heightfield {
+            source,
+            resolution,
+            origin,
+            extent,
+            height_scale,
+            material,
+        } => {
+            let resolution = resolution.map(|[width, depth]| (width, depth));
+            let map = match source {
+                HeightSourceConfig::Image { path } => HeightMap::from_image(path, resolution),
+                HeightSourceConfig::Noise { frequency, octaves } => {
+                    HeightMap::from_noise(resolution.unwrap_or((256, 256)), frequency, octaves)
+                }
+            };
+            GeometryEnum::Heightfield(Heightfield::new(
+                map,
+                origin,
+                extent,
+                height_scale,
+                material_helper(material),
+            ))
         }
-        GeometryConfig::RotateY { instance, angle } => {
-            GeometryEnum::RotateY(RotateY::new(build_geometry(*instance), angle))
+        GeometryConfig::Csg {
+            operation,
+            left,
+            right,
+        } => GeometryEnum::Csg(Csg::new(
+            operation,
+            build_solid(*left, context),
+            build_solid(*right, context),
+        )),
+        GeometryConfig::Instance {
+            prototype,
+            transform,
+        } => {
+            let prototype = context
+                .prototypes
+                .get(&prototype)
+                .unwrap_or_else(|| panic!("未定义的原型 '{prototype}'！"));
+            GeometryEnum::Instance(Instance::new(prototype.clone(), build_transform(transform)))
         }
         GeometryConfig::ConstantMedium {
             boundary,
             density,
             texture,
-        } => GeometryEnum::ConstantMedium(ConstantMedium::new(
-            build_geometry(*boundary),
+            phase,
+        } => GeometryEnum::ConstantMedium(ConstantMedium::with_phase(
+            build_geometry(*boundary, context),
             density,
             build_texture(texture),
+            build_phase(phase),
+        )),
+        GeometryConfig::HeterogeneousMedium {
+            boundary,
+            density,
+            sigma_a,
+            sigma_s,
+            texture,
+            emission,
+            emission_strength,
+            phase,
+        } => GeometryEnum::HeterogeneousMedium(HeterogeneousMedium::new(
+            build_geometry(*boundary, context),
+            build_density(density),
+            sigma_a.unwrap_or(0.0),
+            sigma_s.unwrap_or(1.0),
+            texture.map_or_else(TextureEnum::default, build_texture),
+            build_phase(phase),
+            emission.unwrap_or(Color::zero()) * emission_strength.unwrap_or(1.0),
         )),
     }
 }
 
-pub fn build_world(config: Vec<GeometryConfig>) -> HittableList {
+fn build_solid(config: GeometryConfig, context: &BuildContext) -> SolidEnum {
+    match config {
+        GeometryConfig::Csg {
+            operation,
+            left,
+            right,
+        } => SolidEnum::Csg(Csg::new(
+            operation,
+            build_solid(*left, context),
+            build_solid(*right, context),
+        )),
+        GeometryConfig::Transform {
+            instance,
+            transform,
+            target_transform,
+            keyframes,
+        } => SolidEnum::Transform(Transformed::animated(
+            build_solid(*instance, context),
+            build_animated_transform(transform, target_transform, keyframes),
+        )),
+        config => match build_geometry(config, context) {
+            GeometryEnum::Sphere(sphere) => SolidEnum::Sphere(sphere),
+            GeometryEnum::Cube(cube) => SolidEnum::Cube(cube),
+            _ => panic!("CSG 的操作数只能是 sphere、cube、transform 或 csg！"),
+        },
+    }
+}
+
+// 实例与其他物体一起由调用者放入顶层 BVH，原型内部各自有自己的加速结构
+pub fn build_world(
+    config: Vec<GeometryConfig>,
+    prototypes: Vec<PrototypeConfig>,
+    bvh: BvhOptions,
+    accelerator: AcceleratorKind,
+    cache: Option<MeshCache>,
+) -> HittableList {
+    let mut context = BuildContext {
+        prototypes: HashMap::new(),
+        bvh,
+        accelerator,
+        cache,
+    };
+    // 按顺序构建，后面的原型可以引用前面的原型
+    for PrototypeConfig { name, geometry } in prototypes {
+        let geometry = Arc::new(build_geometry(geometry, &context));
+        if context.prototypes.insert(name.clone(), geometry).is_some() {
+            panic!("原型 '{name}' 重复定义！");
+        }
+    }
     let mut world = HittableList::new();
     for item in config {
-        world.push(build_geometry(item));
+        world.push(build_geometry(item, &context));
     }
-    let mut world_bvh = HittableList::new();
-    world_bvh.push(BvhNode::new(&mut world.list));
-    world_bvh
+    world
 }
@@ -0,0 +1,263 @@
+use serde::Deserialize;
+
+use crate::{
+    aabb::AABB,
+    geometry::{Cube, Sphere, Transformed},
+    hittable::{HitRecord, Hittable},
+    material::MaterialEnum,
+    ray::Ray,
+    vec::{Point3, Vec2},
+};
+
+// 光线在实体内部的一段区间，enter.t <= exit.t
+pub struct Interval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+// 封闭的实体，可以给出光线穿过它的所有区间（按 t 升序、互不重叠）
+pub trait Solid: Hittable {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>>;
+}
+
+pub enum SolidEnum {
+    Sphere(Sphere<MaterialEnum>),
+    Cube(Cube<MaterialEnum>),
+    Transform(Transformed<SolidEnum>),
+    Csg(Csg<SolidEnum>),
+}
+
+impl Hittable for SolidEnum {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        match self {
+            Self::Sphere(g) => g.hit(ray, t_range),
+            Self::Cube(g) => g.hit(ray, t_range),
+            Self::Transform(g) => g.hit(ray, t_range),
+            Self::Csg(g) => g.hit(ray, t_range),
+        }
+    }
+    fn bounding_box(&self) -> &AABB {
+        match self {
+            Self::Sphere(g) => g.bounding_box(),
+            Self::Cube(g) => g.bounding_box(),
+            Self::Transform(g) => g.bounding_box(),
+            Self::Csg(g) => g.bounding_box(),
+        }
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        match self {
+            Self::Sphere(g) => g.bounding_box_during(shutter),
+            Self::Cube(g) => g.bounding_box_during(shutter),
+            Self::Transform(g) => g.bounding_box_during(shutter),
+            Self::Csg(g) => g.bounding_box_during(shutter),
+        }
+    }
+}
+
+impl Solid for SolidEnum {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>> {
+        match self {
+            Self::Sphere(g) => g.intervals(ray),
+            Self::Cube(g) => g.intervals(ray),
+            Self::Transform(g) => g.intervals(ray),
+            Self::Csg(g) => g.intervals(ray),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Self::Union => in_left || in_right,
+            Self::Intersection => in_left && in_right,
+            Self::Difference => in_left && !in_right,
+        }
+    }
+
+    // 由两侧的包围盒得到运算结果的包围盒
+    fn combine(self, a: &AABB, b: &AABB) -> AABB {
+        match self {
+            Self::Union => AABB::from_aabb(a, b),
+            Self::Intersection => {
+                let lower = |axis: usize| a[axis].0.max(b[axis].0);
+                let upper = |axis: usize| a[axis].1.min(b[axis].1).max(lower(axis));
+                AABB::new(
+                    Point3::new(lower(0), lower(1), lower(2)),
+                    Point3::new(upper(0), upper(1), upper(2)),
+                )
+            }
+            Self::Difference => a.clone(),
+        }
+    }
+}
+
+pub struct Csg<S: Solid> {
+    operation: CsgOperation,
+    left: Box<S>,
+    right: Box<S>,
+    bbox: AABB,
+}
+
+impl<S: Solid> Csg<S> {
+    pub fn new(operation: CsgOperation, left: S, right: S) -> Self {
+        let bbox = operation.combine(left.bounding_box(), right.bounding_box());
+        Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+            bbox,
+        }
+    }
+}
+
+impl<S: Solid> Hittable for Csg<S> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        if !self.bbox.hit(ray, t_range) {
+            return None;
+        }
+        self.intervals(ray)
+            .into_iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|rec| rec.t >= t_range.0 && rec.t <= t_range.1)
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.operation.combine(
+            &self.left.bounding_box_during(shutter),
+            &self.right.bounding_box_during(shutter),
+        )
+    }
+}
+
+impl<S: Solid> Solid for Csg<S> {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>> {
+        // 把两侧区间的端点按 t 合并，扫描时根据布尔运算判断进出
+        let mut events: Vec<(HitRecord<'a>, bool, bool)> = vec![];
+        for (is_left, solid) in [(true, &self.left), (false, &self.right)] {
+            for interval in solid.intervals(ray) {
+                events.push((interval.enter, is_left, true));
+                events.push((interval.exit, is_left, false));
+            }
+        }
+        events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter: Option<HitRecord<'a>> = None;
+        let mut result = vec![];
+        for (mut rec, is_left, entering) in events {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            let inside = self.operation.inside(in_left, in_right);
+            // 记录中的法线总是背向光线，只需修正 front_face
+            if !was_inside && inside {
+                rec.front_face = true;
+                enter = Some(rec);
+            } else if was_inside && !inside {
+                rec.front_face = false;
+                if let Some(enter) = enter.take() {
+                    result.push(Interval { enter, exit: rec });
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambertian, texture::TextureEnum, vec::Vec3};
+
+    fn sphere(x: f64) -> SolidEnum {
+        let material = Arc::new(MaterialEnum::Lambertian(Lambertian::new(
+            TextureEnum::default(),
+        )));
+        let center = Point3::from_axis_x(x);
+        SolidEnum::Sphere(Sphere::new(center, center, 1.0, material))
+    }
+
+    // 沿 x 轴发出的光线穿过运算结果的所有区间
+    fn spans(operation: CsgOperation, right: f64, origin: f64) -> Vec<(f64, f64)> {
+        let csg = Csg::new(operation, sphere(0.0), sphere(right));
+        let ray = Ray::new(Point3::from_axis_x(origin), Vec3::from_axis_x(1.0), 0.0);
+        csg.intervals(&ray)
+            .into_iter()
+            .map(|interval| {
+                assert!(interval.enter.front_face && !interval.exit.front_face);
+                (interval.enter.t, interval.exit.t)
+            })
+            .collect()
+    }
+
+    fn first_hit(operation: CsgOperation, right: f64, origin: f64) -> Option<(f64, bool)> {
+        let csg = Csg::new(operation, sphere(0.0), sphere(right));
+        let ray = Ray::new(Point3::from_axis_x(origin), Vec3::from_axis_x(1.0), 0.0);
+        csg.hit(&ray, Vec2::new(0.001, f64::INFINITY))
+            .map(|rec| (rec.t, rec.front_face))
+    }
+
+    fn assert_spans(actual: Vec<(f64, f64)>, expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-9 && (a.1 - e.1).abs() < 1e-9,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_spheres() {
+        // 左球占 x ∈ [-1, 1]，右球占 x ∈ [0, 2]
+        assert_spans(spans(CsgOperation::Union, 1.0, -5.0), &[(4.0, 7.0)]);
+        assert_spans(spans(CsgOperation::Intersection, 1.0, -5.0), &[(5.0, 6.0)]);
+        assert_spans(spans(CsgOperation::Difference, 1.0, -5.0), &[(4.0, 5.0)]);
+    }
+
+    #[test]
+    fn disjoint_spheres() {
+        // 左球占 x ∈ [-1, 1]，右球占 x ∈ [2, 4]
+        assert_spans(
+            spans(CsgOperation::Union, 3.0, -5.0),
+            &[(4.0, 6.0), (7.0, 9.0)],
+        );
+        assert_spans(spans(CsgOperation::Intersection, 3.0, -5.0), &[]);
+        assert_spans(spans(CsgOperation::Difference, 3.0, -5.0), &[(4.0, 6.0)]);
+        assert_eq!(first_hit(CsgOperation::Intersection, 3.0, -5.0), None);
+    }
+
+    #[test]
+    fn ray_starting_inside_operand() {
+        // 起点 x = -0.5 位于左球内部、右球外部
+        let check = |operation, expected: (f64, bool)| {
+            let (t, front_face) = first_hit(operation, 1.0, -0.5).unwrap();
+            assert!((t - expected.0).abs() < 1e-9, "{t} != {}", expected.0);
+            assert_eq!(front_face, expected.1);
+        };
+        check(CsgOperation::Union, (2.5, false));
+        check(CsgOperation::Intersection, (0.5, true));
+        check(CsgOperation::Difference, (0.5, false));
+        // 起点 x = 0.5 位于两球重叠部分，差集在它前方已经没有区间
+        let (t, front_face) = first_hit(CsgOperation::Union, 1.0, 0.5).unwrap();
+        assert!((t - 1.5).abs() < 1e-9 && !front_face);
+        let (t, front_face) = first_hit(CsgOperation::Intersection, 1.0, 0.5).unwrap();
+        assert!((t - 0.5).abs() < 1e-9 && !front_face);
+        assert_eq!(first_hit(CsgOperation::Difference, 1.0, 0.5), None);
+    }
+}
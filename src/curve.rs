@@ -0,0 +1,345 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{
+    aabb::AABB,
+    bvh::{BvhNode, BvhOptions},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    math::{mix, orthonormal_basis},
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveShape {
+    // 总是正对光线的扁平带子
+    Ribbon,
+    // 同样按带子求交，但法线沿宽度方向弯曲，看起来像圆管
+    Tube,
+}
+
+fn lerp(a: Point3, b: Point3, t: f64) -> Point3 {
+    (1.0 - t) * a + t * b
+}
+
+// 三次 Bezier 曲线的开花（blossom），用来取出参数区间上的子曲线
+fn blossom(cp: &[Point3; 4], u0: f64, u1: f64, u2: f64) -> Point3 {
+    let a = [
+        lerp(cp[0], cp[1], u0),
+        lerp(cp[1], cp[2], u0),
+        lerp(cp[2], cp[3], u0),
+    ];
+    let b = [lerp(a[0], a[1], u1), lerp(a[1], a[2], u1)];
+    lerp(b[0], b[1], u2)
+}
+
+fn sub_curve(cp: &[Point3; 4], u0: f64, u1: f64) -> [Point3; 4] {
+    [
+        blossom(cp, u0, u0, u0),
+        blossom(cp, u0, u0, u1),
+        blossom(cp, u0, u1, u1),
+        blossom(cp, u1, u1, u1),
+    ]
+}
+
+// 返回曲线上 u 处的点和导数
+fn evaluate(cp: &[Point3; 4], u: f64) -> (Point3, Vec3) {
+    let a = [
+        lerp(cp[0], cp[1], u),
+        lerp(cp[1], cp[2], u),
+        lerp(cp[2], cp[3], u),
+    ];
+    let b = [lerp(a[0], a[1], u), lerp(a[1], a[2], u)];
+    // 端点处控制点重合时导数退化，改用弦的方向
+    let derivative = if (b[1] - b[0]).length_squared() > 0.0 {
+        3.0 * (b[1] - b[0])
+    } else {
+        cp[3] - cp[0]
+    };
+    (lerp(b[0], b[1], u), derivative)
+}
+
+// 曲线在 [u_min, u_max] 上的一段，作为 BVH 中的一个图元
+struct CurveSegment<M: Material> {
+    points: [Point3; 4],
+    // 整条曲线两端的宽度
+    width: Vec2,
+    u_min: f64,
+    u_max: f64,
+    shape: CurveShape,
+    material: Arc<M>,
+    bbox: AABB,
+}
+
+impl<M: Material> CurveSegment<M> {
+    fn width_at(&self, u: f64) -> f64 {
+        mix(self.width.0, self.width.1, u)
+    }
+
+    // cp 为光线空间中的控制点：光线起点在原点，沿 +z 方向，z 即为距离
+    fn recursive_hit(
+        &self,
+        cp: &[Point3; 4],
+        u0: f64,
+        u1: f64,
+        depth: u32,
+        z_range: Vec2,
+        max_width: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let half_width = max_width / 2.0;
+        let lower = cp
+            .iter()
+            .fold(Point3::from_single(f64::INFINITY), |a, p| a.min(*p));
+        let upper = cp
+            .iter()
+            .fold(Point3::from_single(f64::NEG_INFINITY), |a, p| a.max(*p));
+        if lower.0 - half_width > 0.0
+            || upper.0 + half_width < 0.0
+            || lower.1 - half_width > 0.0
+            || upper.1 + half_width < 0.0
+            || lower.2 - half_width > z_range.1
+            || upper.2 + half_width < z_range.0
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let middle = 0.5 * (u0 + u1);
+            let halves = [
+                (sub_curve(cp, 0.0, 0.5), u0, middle),
+                (sub_curve(cp, 0.5, 1.0), middle, u1),
+            ];
+            let mut closest: Option<(f64, f64, f64)> = None;
+            for (half, u0, u1) in halves {
+                let z_max = closest.map_or(z_range.1, |hit| hit.0);
+                if let Some(hit) = self.recursive_hit(
+                    &half,
+                    u0,
+                    u1,
+                    depth - 1,
+                    Vec2::new(z_range.0, z_max),
+                    max_width,
+                ) {
+                    closest = Some(hit);
+                }
+            }
+            return closest;
+        }
+
+        // 足够平直后当作线段，原点需位于两端垂直于切线的平面之间
+        let edge = |p: Point3, q: Point3| (q.1 - p.1) * -p.1 + p.0 * (p.0 - q.0);
+        if edge(cp[0], cp[1]) < 0.0 || edge(cp[3], cp[2]) < 0.0 {
+            return None;
+        }
+        let segment = Vec2::new(cp[3].0 - cp[0].0, cp[3].1 - cp[0].1);
+        let denominator = segment.length_squared();
+        if denominator == 0.0 {
+            return None;
+        }
+        let w = ((-cp[0].0 * segment.0 - cp[0].1 * segment.1) / denominator).clamp(0.0, 1.0);
+        let u = mix(u0, u1, w);
+        let width = self.width_at(u);
+        let (center, derivative) = evaluate(cp, w);
+        let distance_squared = center.0 * center.0 + center.1 * center.1;
+        if distance_squared > width * width / 4.0 || center.2 < z_range.0 || center.2 > z_range.1 {
+            return None;
+        }
+        // 原点在切线哪一侧决定 v 在 0.5 的哪一边
+        let side = derivative.0 * -center.1 - derivative.1 * -center.0;
+        let offset = distance_squared.sqrt() / width;
+        let v = if side > 0.0 {
+            0.5 + offset
+        } else {
+            0.5 - offset
+        };
+        Some((center.2, u, v))
+    }
+}
+
+impl<M: Material + 'static> Hittable for CurveSegment<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        if !self.bbox.hit(ray, t_range) {
+            return None;
+        }
+        let length = ray.direction.length();
+        let forward = ray.direction / length;
+        let (right, up) = orthonormal_basis(forward);
+        let to_ray_space = |p: Point3| {
+            let local = p - ray.origin;
+            Point3::new(local.dot(right), local.dot(up), local.dot(forward))
+        };
+        let cp = self.points.map(to_ray_space);
+
+        // 细分深度取决于曲线的弯曲程度与宽度之比
+        let max_width = self.width_at(self.u_min).max(self.width_at(self.u_max));
+        let flatness = (0..2)
+            .map(|i| {
+                let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+                d.0.abs().max(d.1.abs()).max(d.2.abs())
+            })
+            .fold(0.0, f64::max);
+        let epsilon = max_width / 20.0;
+        let depth = if flatness > 0.0 {
+            ((2.0_f64.sqrt() * 6.0 * flatness / (8.0 * epsilon)).log2() / 2.0).clamp(0.0, 10.0)
+        } else {
+            0.0
+        };
+
+        let (z, u, v) = self.recursive_hit(
+            &cp,
+            self.u_min,
+            self.u_max,
+            depth.round() as u32,
+            t_range * length,
+            max_width,
+        )?;
+        let t = z / length;
+
+        let (_, derivative) = evaluate(&self.points, (u - self.u_min) / (self.u_max - self.u_min));
+        let tangent = derivative.normalize();
+        let side = tangent.cross(-forward).normalize();
+        let facing = side.cross(tangent);
+        let normal = match self.shape {
+            CurveShape::Ribbon => facing,
+            CurveShape::Tube => {
+                let theta = (v - 0.5) * PI;
+                theta.cos() * facing + theta.sin() * side
+            }
+        };
+        Some(HitRecord {
+            p: ray.at(t),
+            normal,
+            t,
+            material: self.material.as_ref(),
+            front_face: true,
+            uv: Vec2::new(u, v),
+            tangent: Some(tangent),
+            vertex_color: None,
+        })
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+pub struct CurveData {
+    pub points: [Point3; 4],
+    // 起点和终点处的宽度，中间线性插值
+    pub width: Vec2,
+}
+
+// 一组 Bezier 曲线，每条曲线切成 segments 段后放进同一个 BVH
+pub struct Curves {
+    bvh: BvhNode,
+}
+
+impl Curves {
+    pub fn new<M: Material + 'static>(
+        curves: Vec<CurveData>,
+        shape: CurveShape,
+        segments: usize,
+        material: Arc<M>,
+        options: &BvhOptions,
+    ) -> Self {
+        assert!(!curves.is_empty(), "Curves has no curve.");
+        let segments = segments.max(1);
+        let list: Vec<Arc<dyn Hittable>> = curves
+            .iter()
+            .flat_map(|curve| {
+                let material = material.clone();
+                (0..segments).map(move |i| {
+                    let u_min = i as f64 / segments as f64;
+                    let u_max = (i + 1) as f64 / segments as f64;
+                    let points = sub_curve(&curve.points, u_min, u_max);
+                    let half_width = mix(curve.width.0, curve.width.1, u_min).max(mix(
+                        curve.width.0,
+                        curve.width.1,
+                        u_max,
+                    )) / 2.0;
+                    let lower = points
+                        .iter()
+                        .fold(Point3::from_single(f64::INFINITY), |a, p| a.min(*p));
+                    let upper = points
+                        .iter()
+                        .fold(Point3::from_single(f64::NEG_INFINITY), |a, p| a.max(*p));
+                    Arc::new(CurveSegment {
+                        points,
+                        width: curve.width,
+                        u_min,
+                        u_max,
+                        shape,
+                        material: material.clone(),
+                        bbox: AABB::new(
+                            lower - Vec3::from_single(half_width),
+                            upper + Vec3::from_single(half_width),
+                        ),
+                    }) as Arc<dyn Hittable>
+                })
+            })
+            .collect();
+        Curves {
+            bvh: BvhNode::new(&list, options),
+        }
+    }
+}
+
+impl Hittable for Curves {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        self.bvh.hit(ray, t_range)
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.bvh.occluded(ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        self.bvh.bounding_box()
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.bvh.bounding_box_during(shutter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, texture::SolidTexture};
+
+    #[test]
+    fn straight_curve_hit_matches_width() {
+        // 沿 x 轴的直线，宽度从 0.2 线性变到 0.6
+        let points = [-2.0, -2.0 / 3.0, 2.0 / 3.0, 2.0].map(Point3::from_axis_x);
+        let curves = Curves::new(
+            vec![CurveData {
+                points,
+                width: Vec2::new(0.2, 0.6),
+            }],
+            CurveShape::Ribbon,
+            4,
+            Arc::new(Lambertian::new(SolidTexture::new(Vec3::from_single(0.5)))),
+            &BvhOptions::default(),
+        );
+        let t_range = Vec2::new(0.001, f64::INFINITY);
+        let ray_at =
+            |x: f64, y: f64| Ray::new(Point3::new(x, y, 5.0), Vec3::from_axis_z(-2.0), 0.0);
+        for x in [-1.5, -0.5, 0.0, 1.0, 1.8] {
+            let u = (x + 2.0) / 4.0;
+            let half_width = mix(0.2, 0.6, u) / 2.0;
+            for y in [0.0, 0.9 * half_width, -0.9 * half_width] {
+                let rec = curves.hit(&ray_at(x, y), t_range).unwrap();
+                assert!((rec.t - 2.5).abs() < 1e-9, "{} != 2.5", rec.t);
+                assert!((rec.uv.0 - u).abs() < 1e-6, "{} != {u}", rec.uv.0);
+                let offset = (rec.uv.1 - 0.5).abs();
+                assert!((offset - y.abs() / (2.0 * half_width)).abs() < 1e-6);
+                assert!(rec.normal.dot(Vec3::from_axis_z(1.0)) > 0.999);
+                assert!(rec.tangent.unwrap().dot(Vec3::from_axis_x(1.0)) > 0.999);
+            }
+            assert!(curves.hit(&ray_at(x, 1.1 * half_width), t_range).is_none());
+            assert!(curves.hit(&ray_at(x, -1.1 * half_width), t_range).is_none());
+        }
+        // 超出两端的光线不相交
+        assert!(curves.hit(&ray_at(2.1, 0.0), t_range).is_none());
+        assert!(curves.hit(&ray_at(-2.1, 0.0), t_range).is_none());
+    }
+}
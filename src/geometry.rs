@@ -1,24 +1,49 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
     aabb::AABB,
+    accelerator::{AcceleratorEnum, AcceleratorKind},
+    csg::{Csg, Interval, Solid, SolidEnum},
+    curve::Curves,
+    heightfield::Heightfield,
     hittable::{HitRecord, Hittable},
-    material::{Isotropic, Material, MaterialEnum},
-    math::{get_sphere_uv, mix},
+    material::{Material, MaterialEnum, PhaseFunction, PhaseMaterial},
+    math::{get_sphere_uv, mix, orthonormal_basis},
     matrix::Mat33,
-    random::m_random,
+    medium::{DensityEnum, HeterogeneousMedium},
+    mesh::{Mesh, moller_trumbore},
+    quadric::{Cone, Cylinder, Torus},
+    random::{m_random, random_in_unit_ring},
     ray::Ray,
+    sdf::Sdf,
     texture::{Texture, TextureEnum},
+    transform::{AnimatedTransform, Keyframes, Transform},
     vec::{Point3, Vec2, Vec3},
+    voxel::VoxelGrid,
 };
 
 pub enum GeometryEnum {
     Sphere(Sphere<MaterialEnum>),
     Quad(Quad<MaterialEnum>),
+    Ellipse(Ellipse<MaterialEnum>),
     Cube(Cube<MaterialEnum>),
+    Triangle(Triangle<MaterialEnum>),
+    Mesh(Mesh),
+    Cylinder(Cylinder<MaterialEnum>),
+    Cone(Cone<MaterialEnum>),
+    Torus(Torus<MaterialEnum>),
+    Sdf(Sdf<MaterialEnum>),
+    Heightfield(Heightfield<MaterialEnum>),
+    Voxel(VoxelGrid<MaterialEnum>),
+    Curves(Curves),
     Translate(Translate<GeometryEnum>),
     RotateY(RotateY<GeometryEnum>),
+    Transform(Transformed<GeometryEnum>),
+    Group(Group),
+    Csg(Csg<SolidEnum>),
+    Instance(Instance<GeometryEnum>),
     ConstantMedium(ConstantMedium<GeometryEnum, TextureEnum>),
+    HeterogeneousMedium(HeterogeneousMedium<GeometryEnum, DensityEnum, TextureEnum>),
 }
 
 impl Hittable for GeometryEnum {
@@ -26,20 +51,100 @@ impl Hittable for GeometryEnum {
         match self {
             Self::Sphere(g) => g.hit(ray, t_range),
             Self::Quad(g) => g.hit(ray, t_range),
+            Self::Ellipse(g) => g.hit(ray, t_range),
             Self::Cube(g) => g.hit(ray, t_range),
+            Self::Triangle(g) => g.hit(ray, t_range),
+            Self::Mesh(g) => g.hit(ray, t_range),
+            Self::Cylinder(g) => g.hit(ray, t_range),
+            Self::Cone(g) => g.hit(ray, t_range),
+            Self::Torus(g) => g.hit(ray, t_range),
+            Self::Sdf(g) => g.hit(ray, t_range),
+            Self::Heightfield(g) => g.hit(ray, t_range),
+            Self::Voxel(g) => g.hit(ray, t_range),
+            Self::Curves(g) => g.hit(ray, t_range),
             Self::Translate(g) => g.hit(ray, t_range),
             Self::RotateY(g) => g.hit(ray, t_range),
+            Self::Transform(g) => g.hit(ray, t_range),
+            Self::Group(g) => g.hit(ray, t_range),
+            Self::Csg(g) => g.hit(ray, t_range),
+            Self::Instance(g) => g.hit(ray, t_range),
             Self::ConstantMedium(g) => g.hit(ray, t_range),
+            Self::HeterogeneousMedium(g) => g.hit(ray, t_range),
+        }
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        match self {
+            Self::Sphere(g) => g.occluded(ray, t_range),
+            Self::Quad(g) => g.occluded(ray, t_range),
+            Self::Ellipse(g) => g.occluded(ray, t_range),
+            Self::Cube(g) => g.occluded(ray, t_range),
+            Self::Triangle(g) => g.occluded(ray, t_range),
+            Self::Mesh(g) => g.occluded(ray, t_range),
+            Self::Cylinder(g) => g.occluded(ray, t_range),
+            Self::Cone(g) => g.occluded(ray, t_range),
+            Self::Torus(g) => g.occluded(ray, t_range),
+            Self::Sdf(g) => g.occluded(ray, t_range),
+            Self::Heightfield(g) => g.occluded(ray, t_range),
+            Self::Voxel(g) => g.occluded(ray, t_range),
+            Self::Curves(g) => g.occluded(ray, t_range),
+            Self::Translate(g) => g.occluded(ray, t_range),
+            Self::RotateY(g) => g.occluded(ray, t_range),
+            Self::Transform(g) => g.occluded(ray, t_range),
+            Self::Group(g) => g.occluded(ray, t_range),
+            Self::Csg(g) => g.occluded(ray, t_range),
+            Self::Instance(g) => g.occluded(ray, t_range),
+            Self::ConstantMedium(g) => g.occluded(ray, t_range),
+            Self::HeterogeneousMedium(g) => g.occluded(ray, t_range),
         }
     }
     fn bounding_box(&self) -> &AABB {
         match self {
             Self::Sphere(g) => g.bounding_box(),
             Self::Quad(g) => g.bounding_box(),
+            Self::Ellipse(g) => g.bounding_box(),
             Self::Cube(g) => g.bounding_box(),
+            Self::Triangle(g) => g.bounding_box(),
+            Self::Mesh(g) => g.bounding_box(),
+            Self::Cylinder(g) => g.bounding_box(),
+            Self::Cone(g) => g.bounding_box(),
+            Self::Torus(g) => g.bounding_box(),
+            Self::Sdf(g) => g.bounding_box(),
+            Self::Heightfield(g) => g.bounding_box(),
+            Self::Voxel(g) => g.bounding_box(),
+            Self::Curves(g) => g.bounding_box(),
             Self::Translate(g) => g.bounding_box(),
             Self::RotateY(g) => g.bounding_box(),
+            Self::Transform(g) => g.bounding_box(),
+            Self::Group(g) => g.bounding_box(),
+            Self::Csg(g) => g.bounding_box(),
+            Self::Instance(g) => g.bounding_box(),
             Self::ConstantMedium(g) => g.bounding_box(),
+            Self::HeterogeneousMedium(g) => g.bounding_box(),
+        }
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        match self {
+            Self::Sphere(g) => g.bounding_box_during(shutter),
+            Self::Quad(g) => g.bounding_box_during(shutter),
+            Self::Ellipse(g) => g.bounding_box_during(shutter),
+            Self::Cube(g) => g.bounding_box_during(shutter),
+            Self::Triangle(g) => g.bounding_box_during(shutter),
+            Self::Mesh(g) => g.bounding_box_during(shutter),
+            Self::Cylinder(g) => g.bounding_box_during(shutter),
+            Self::Cone(g) => g.bounding_box_during(shutter),
+            Self::Torus(g) => g.bounding_box_during(shutter),
+            Self::Sdf(g) => g.bounding_box_during(shutter),
+            Self::Heightfield(g) => g.bounding_box_during(shutter),
+            Self::Voxel(g) => g.bounding_box_during(shutter),
+            Self::Curves(g) => g.bounding_box_during(shutter),
+            Self::Translate(g) => g.bounding_box_during(shutter),
+            Self::RotateY(g) => g.bounding_box_during(shutter),
+            Self::Transform(g) => g.bounding_box_during(shutter),
+            Self::Group(g) => g.bounding_box_during(shutter),
+            Self::Csg(g) => g.bounding_box_during(shutter),
+            Self::Instance(g) => g.bounding_box_during(shutter),
+            Self::ConstantMedium(g) => g.bounding_box_during(shutter),
+            Self::HeterogeneousMedium(g) => g.bounding_box_during(shutter),
         }
     }
 }
@@ -67,70 +172,183 @@ impl<M: Material> Sphere<M> {
     }
 }
 
-impl<M: Material + 'static> Hittable for Sphere<M> {
-    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
-        let current_center = Vec3::mix(self.center, self.target_center, ray.time);
-        let oc = current_center - ray.origin;
+impl<M: Material + 'static> Sphere<M> {
+    // 返回光线与球面的两个交点参数 (t0, t1)，t0 <= t1
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let oc = self.current_center(ray.time) - ray.origin;
         let a = ray.direction.length_squared();
         let h = ray.direction.dot(oc);
         let c = oc.length_squared() - self.radius * self.radius;
         let k = h * h - a * c;
         if k < 0.0 {
-            None
-        } else {
-            let k = k.sqrt();
-            let helper = |solve: f64| {
-                if solve >= t_range.0 && solve <= t_range.1 {
-                    let p = ray.at(solve);
-                    let normal = (p - current_center).normalize();
-                    let front_face = ray.direction.dot(normal) < 0.0;
-                    Some(HitRecord {
-                        p,
-                        normal: if front_face { normal } else { -normal },
-                        t: solve,
-                        material: self.material.as_ref(),
-                        front_face,
-                        uv: get_sphere_uv(normal),
-                    })
-                } else {
-                    None
-                }
-            };
-            helper((h - k) / a).or(helper((h + k) / a))
+            return None;
+        }
+        let k = k.sqrt();
+        Some(((h - k) / a, (h + k) / a))
+    }
+
+    fn current_center(&self, time: f64) -> Point3 {
+        Vec3::mix(self.center, self.target_center, time)
+    }
+
+    fn record_at<'a>(&'a self, ray: &Ray, t: f64) -> HitRecord<'a> {
+        let p = ray.at(t);
+        let normal = (p - self.current_center(ray.time)).normalize();
+        let front_face = ray.direction.dot(normal) < 0.0;
+        HitRecord {
+            p,
+            normal: if front_face { normal } else { -normal },
+            t,
+            material: self.material.as_ref(),
+            front_face,
+            uv: get_sphere_uv(normal),
+            tangent: None,
+            vertex_color: None,
         }
     }
+}
+
+impl<M: Material + 'static> Solid for Sphere<M> {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>> {
+        self.solve(ray).map_or(vec![], |(t0, t1)| {
+            let (mut enter, mut exit) = (self.record_at(ray, t0), self.record_at(ray, t1));
+            enter.front_face = true;
+            exit.front_face = false;
+            vec![Interval { enter, exit }]
+        })
+    }
+}
+
+impl<M: Material + 'static> Hittable for Sphere<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let (t0, t1) = self.solve(ray)?;
+        [t0, t1]
+            .into_iter()
+            .find(|t| *t >= t_range.0 && *t <= t_range.1)
+            .map(|t| self.record_at(ray, t))
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        let [a, b] = [shutter.0, shutter.1].map(|time| self.current_center(time));
+        AABB::from_aabb(
+            &AABB::new(a - self.radius, a + self.radius),
+            &AABB::new(b - self.radius, b + self.radius),
+        )
+    }
 }
 
-pub struct Quad<M: Material> {
+// 平面形状，在以 u、v 为轴的平面坐标 (alpha, beta) 下判断内部并采样
+pub trait PlanarShape: Send + Sync {
+    fn contains(&self, planar: Vec2) -> bool;
+    // 命中点的纹理坐标
+    fn uv(&self, planar: Vec2) -> Vec2 {
+        planar
+    }
+    // 形状面积与 |u × v| 之比
+    fn area_ratio(&self) -> f64;
+    // 在形状内均匀采样平面坐标
+    fn sample(&self) -> Vec2;
+    // 平面坐标的包围范围 (min, max)
+    fn bounds(&self) -> (Vec2, Vec2);
+    // 光线与平面的交点参数和平面坐标，默认先求与平面的交点再投影到 u、v 上
+    fn intersect(&self, plane: &Plane, ray: &Ray, t_range: Vec2) -> Option<(f64, Vec2)> {
+        plane.intersect(ray, t_range)
+    }
+}
+
+// 以 q 为顶点、u、v 为边的平行四边形
+pub struct ParallelogramShape;
+
+impl PlanarShape for ParallelogramShape {
+    fn contains(&self, planar: Vec2) -> bool {
+        (0.0..=1.0).contains(&planar.0) && (0.0..=1.0).contains(&planar.1)
+    }
+    fn area_ratio(&self) -> f64 {
+        1.0
+    }
+    fn sample(&self) -> Vec2 {
+        Vec2::new(m_random(), m_random())
+    }
+    fn bounds(&self) -> (Vec2, Vec2) {
+        (Vec2::zero(), Vec2::one())
+    }
+}
+
+// 顶点为 q、q + u、q + v 的三角形，平面坐标即重心坐标
+pub struct TriangleShape;
+
+impl PlanarShape for TriangleShape {
+    fn contains(&self, planar: Vec2) -> bool {
+        planar.0 >= 0.0 && planar.1 >= 0.0 && planar.0 + planar.1 <= 1.0
+    }
+    fn area_ratio(&self) -> f64 {
+        0.5
+    }
+    fn sample(&self) -> Vec2 {
+        let s = m_random::<f64>().sqrt();
+        Vec2::new(1.0 - s, m_random::<f64>() * s)
+    }
+    fn bounds(&self) -> (Vec2, Vec2) {
+        (Vec2::zero(), Vec2::one())
+    }
+    // 与网格三角形一样用 Möller–Trumbore，不需要先求平面交点
+    fn intersect(&self, plane: &Plane, ray: &Ray, t_range: Vec2) -> Option<(f64, Vec2)> {
+        let (t, u, v) = moller_trumbore(ray, plane.q, plane.edge.0, plane.edge.1, t_range)?;
+        Some((t, Vec2::new(u, v)))
+    }
+}
+
+// 以 q 为中心、u、v 为半轴的椭圆，inner_ratio 大于 0 时为椭圆环
+// 纹理坐标为 (角度, 从内到外的半径)
+pub struct EllipseShape {
+    pub inner_ratio: f64,
+}
+
+impl PlanarShape for EllipseShape {
+    fn contains(&self, planar: Vec2) -> bool {
+        (self.inner_ratio * self.inner_ratio..=1.0).contains(&planar.length_squared())
+    }
+    fn uv(&self, planar: Vec2) -> Vec2 {
+        Vec2::new(
+            (planar.1.atan2(planar.0) / (2.0 * PI)).rem_euclid(1.0),
+            (planar.length() - self.inner_ratio) / (1.0 - self.inner_ratio),
+        )
+    }
+    fn area_ratio(&self) -> f64 {
+        PI * (1.0 - self.inner_ratio * self.inner_ratio)
+    }
+    fn sample(&self) -> Vec2 {
+        random_in_unit_ring(self.inner_ratio)
+    }
+    fn bounds(&self) -> (Vec2, Vec2) {
+        (Vec2::from_single(-1.0), Vec2::one())
+    }
+}
+
+// 以 q 为原点、u、v 为轴的平面
+pub struct Plane {
     q: Vec3,
     edge: (Vec3, Vec3),
-    material: Arc<M>,
-    bbox: AABB,
     normal: Vec3,
     constant_d: f64,
     constant_w: Vec3,
 }
 
-impl<M: Material> Quad<M> {
-    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Arc<M>) -> Self {
+impl Plane {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3) -> Self {
         let normal = u.cross(v);
-        Quad {
+        Plane {
             q,
             edge: (u, v),
-            material,
-            bbox: AABB::from_aabb(&AABB::new(q, q + u + v), &AABB::new(q + u, q + v)),
             normal,
             constant_d: normal.dot(q),
             constant_w: normal / normal.dot(normal),
         }
     }
-}
 
-impl<M: Material + 'static> Hittable for Quad<M> {
-    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+    pub fn intersect(&self, ray: &Ray, t_range: Vec2) -> Option<(f64, Vec2)> {
         let (n, w) = (self.normal, self.constant_w);
         let denominator = n.dot(ray.direction);
         if denominator.abs() < 1e-8 {
@@ -140,30 +358,120 @@ impl<M: Material + 'static> Hittable for Quad<M> {
         if t < t_range.0 || t > t_range.1 {
             return None;
         }
-        let front_face = denominator < 0.0;
-        let p = ray.at(t);
-        let u_t = (p - self.q).cross(self.edge.1).dot(w);
-        let v_t = (self.edge.0.cross(p - self.q)).dot(w);
-        if u_t >= 0.0 && u_t <= 1.0 && v_t >= 0.0 && v_t <= 1.0 {
-            Some(HitRecord {
-                p,
-                normal: (if front_face { n } else { -n }).normalize(),
-                t,
-                material: self.material.as_ref(),
-                front_face,
-                uv: Vec2::new(u_t, v_t),
-            })
-        } else {
-            None
+        let p = ray.at(t) - self.q;
+        let u_t = p.cross(self.edge.1).dot(w);
+        let v_t = self.edge.0.cross(p).dot(w);
+        Some((t, Vec2::new(u_t, v_t)))
+    }
+}
+
+// 所有平面图元共享的平面求交，形状只决定内部判断
+pub struct Planar<S: PlanarShape, M: Material> {
+    plane: Plane,
+    shape: S,
+    material: Arc<M>,
+    bbox: AABB,
+}
+
+pub type Quad<M> = Planar<ParallelogramShape, M>;
+pub type Triangle<M> = Planar<TriangleShape, M>;
+pub type Ellipse<M> = Planar<EllipseShape, M>;
+
+impl<M: Material> Planar<ParallelogramShape, M> {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Arc<M>) -> Self {
+        Planar::with_shape(q, u, v, ParallelogramShape, material)
+    }
+}
+
+impl<M: Material> Planar<TriangleShape, M> {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<M>) -> Self {
+        Planar::with_shape(a, b - a, c - a, TriangleShape, material)
+    }
+}
+
+impl<M: Material> Planar<EllipseShape, M> {
+    // 圆盘是两个半轴等长且垂直的椭圆
+    pub fn disk(
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        inner_radius: f64,
+        material: Arc<M>,
+    ) -> Self {
+        let (u, v) = orthonormal_basis(normal.normalize());
+        let shape = EllipseShape {
+            inner_ratio: inner_radius / radius,
+        };
+        Planar::with_shape(center, u * radius, v * radius, shape, material)
+    }
+}
+
+impl<S: PlanarShape, M: Material> Planar<S, M> {
+    pub fn with_shape(q: Vec3, u: Vec3, v: Vec3, shape: S, material: Arc<M>) -> Self {
+        let (min, max) = shape.bounds();
+        let corner = |a: f64, b: f64| q + a * u + b * v;
+        Planar {
+            plane: Plane::new(q, u, v),
+            bbox: AABB::from_aabb(
+                &AABB::new(corner(min.0, min.1), corner(max.0, max.1)),
+                &AABB::new(corner(max.0, min.1), corner(min.0, max.1)),
+            ),
+            shape,
+            material,
         }
     }
+
+    pub fn area(&self) -> f64 {
+        self.plane.normal.length() * self.shape.area_ratio()
+    }
+
+    // 作为面光源时，从 origin 向形状内均匀采样的点发出的方向
+    pub fn random(&self, origin: Point3) -> Vec3 {
+        let planar = self.shape.sample();
+        let (u, v) = self.plane.edge;
+        self.plane.q + planar.0 * u + planar.1 * v - origin
+    }
+}
+
+impl<S: PlanarShape, M: Material + 'static> Planar<S, M> {
+    // random 采样的方向对应的立体角概率密度，方向没有落在形状上时为 0
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction, 0.0);
+        let Some(rec) = self.hit(&ray, Vec2::new(0.001, f64::INFINITY)) else {
+            return 0.0;
+        };
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area())
+    }
+}
+
+impl<S: PlanarShape, M: Material + 'static> Hittable for Planar<S, M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let (t, planar) = self.shape.intersect(&self.plane, ray, t_range)?;
+        if !self.shape.contains(planar) {
+            return None;
+        }
+        let n = self.plane.normal;
+        let front_face = n.dot(ray.direction) < 0.0;
+        Some(HitRecord {
+            p: ray.at(t),
+            normal: (if front_face { n } else { -n }).normalize(),
+            t,
+            material: self.material.as_ref(),
+            front_face,
+            uv: self.shape.uv(planar),
+            tangent: None,
+            vertex_color: None,
+        })
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
 }
 
 pub struct Cube<M: Material> {
-    faces: [Quad<M>; 6],
+    faces: Box<[Quad<M>; 6]>,
     bbox: AABB,
 }
 
@@ -174,14 +482,14 @@ impl<M: Material> Cube<M> {
         let dy = Vec3::from_axis_y(max.1 - min.1);
         let dz = Vec3::from_axis_z(max.2 - min.2);
         Cube {
-            faces: [
+            faces: Box::new([
                 Quad::new(min, dx, dz, material.clone()),       // bottom
                 Quad::new(min + dy, dz, dx, material.clone()),  // top
                 Quad::new(min + dz, dy, -dz, material.clone()), // left
                 Quad::new(min + dx, dy, dz, material.clone()),  // right
                 Quad::new(min + dz, dx, dy, material.clone()),  // front
                 Quad::new(min, dy, dx, material.clone()),       // front
-            ],
+            ]),
             bbox: AABB::new(a, b),
         }
     }
@@ -199,21 +507,55 @@ impl<M: Material + 'static> Hittable for Cube<M> {
         }
         result
     }
+    // 逐个测试各面，与 hit 的区间规则一致，也不受包围盒填充的影响
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.faces.iter().any(|face| face.occluded(ray, t_range))
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
 }
 
+impl<M: Material + 'static> Solid for Cube<M> {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>> {
+        // 凸体最多有一段区间：最近和最远的面交点
+        let mut hits: Vec<HitRecord> = self
+            .faces
+            .iter()
+            .filter_map(|face| face.hit(ray, Vec2::new(f64::NEG_INFINITY, f64::INFINITY)))
+            .collect();
+        if hits.len() < 2 {
+            return vec![];
+        }
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        let mut exit = hits.pop().unwrap();
+        let mut enter = hits.swap_remove(0);
+        enter.front_face = true;
+        exit.front_face = false;
+        vec![Interval { enter, exit }]
+    }
+}
+
 pub struct Translate<G: Hittable> {
     instance: Box<G>,
-    offset: Vec3,
+    offset: Keyframes<Vec3>,
     bbox: AABB,
 }
 
 impl<G: Hittable> Translate<G> {
     pub fn new(instance: G, offset: Vec3) -> Self {
+        Translate::with_keyframes(instance, Keyframes::constant(offset))
+    }
+
+    // 关键帧之间线性插值，包围盒为各关键帧处包围盒的并
+    pub fn with_keyframes(instance: G, offset: Keyframes<Vec3>) -> Self {
+        let bbox = offset
+            .values()
+            .map(|offset| instance.bounding_box().clone() + offset)
+            .reduce(|a, b| AABB::from_aabb(&a, &b))
+            .unwrap();
         Translate {
-            bbox: instance.bounding_box().clone() + offset,
+            bbox,
             instance: Box::new(instance),
             offset,
         }
@@ -222,78 +564,126 @@ impl<G: Hittable> Translate<G> {
 
 impl<G: Hittable> Hittable for Translate<G> {
     fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
-        let ray = ray.clone() - self.offset;
+        let offset = self.offset.sample(ray.time, Vec3::mix);
+        let ray = ray.clone() - offset;
         self.instance.hit(&ray, t_range).map(|mut rec| {
-            rec.p += self.offset;
+            rec.p += offset;
             rec
         })
     }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let offset = self.offset.sample(ray.time, Vec3::mix);
+        self.instance.occluded(&(ray.clone() - offset), t_range)
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        let bbox = self.instance.bounding_box_during(shutter);
+        self.offset
+            .times_during(shutter)
+            .into_iter()
+            .map(|time| bbox.clone() + self.offset.sample(time, Vec3::mix))
+            .reduce(|a, b| AABB::from_aabb(&a, &b))
+            .unwrap()
+    }
+}
+
+// 绕 Y 轴旋转 angle（弧度）的矩阵
+fn rotation_y(angle: f64) -> Mat33 {
+    let [sin_theta, cos_theta] = [angle.sin(), angle.cos()];
+    Mat33::new([
+        [cos_theta, 0.0, sin_theta],
+        [0.0, 1.0, 0.0],
+        [-sin_theta, 0.0, cos_theta],
+    ])
+}
+
+// bbox 绕 Y 轴转过 ranges 中各个角度区间时扫过的包围盒
+fn rotated_aabb(bbox: &AABB, ranges: &[(f64, f64)]) -> AABB {
+    let [a, b] = [
+        Vec3::new(bbox[0].0, bbox[1].0, bbox[2].0),
+        Vec3::new(bbox[0].1, bbox[1].1, bbox[2].1),
+    ];
+    // 旋转后的点位于角度 alpha - theta 处，角度区间内经过坐标轴时取到极值
+    let mut x_interval = Vec2(f64::MAX, f64::MIN);
+    let mut z_interval = x_interval;
+    for i in 0..2 {
+        for k in 0..2 {
+            let x = mix(a.0, b.0, i as f64);
+            let z = mix(a.2, b.2, k as f64);
+            let (radius, alpha) = ((x * x + z * z).sqrt(), z.atan2(x));
+            for &(low, high) in ranges {
+                let (low, high) = (alpha - high, alpha - low);
+                let quarter = PI / 2.0;
+                let axes = ((low / quarter).ceil() as i64..=(high / quarter).floor() as i64)
+                    .map(|k| k as f64 * quarter);
+                for phi in [low, high].into_iter().chain(axes) {
+                    let (px, pz) = (radius * phi.cos(), radius * phi.sin());
+                    x_interval = Vec2::new(x_interval.0.min(px), x_interval.1.max(px));
+                    z_interval = Vec2::new(z_interval.0.min(pz), z_interval.1.max(pz));
+                }
+            }
+        }
+    }
+    AABB::new(
+        Point3::new(x_interval.0, a.1, z_interval.0),
+        Point3::new(x_interval.1, b.1, z_interval.1),
+    )
 }
 
 pub struct RotateY<G: Hittable> {
     instance: Box<G>,
     bbox: AABB,
+    // 弧度制
+    angle: Keyframes<f64>,
     rot_mat: Mat33,
     reverse_mat: Mat33,
 }
 
 impl<G: Hittable> RotateY<G> {
     pub fn new(instance: G, angle: f64) -> Self {
-        let angle = angle.to_radians();
-        let [sin_theta, cos_theta] = [angle.sin(), angle.cos()];
-        let rot_mat = Mat33::new([
-            [cos_theta, 0.0, sin_theta],
-            [0.0, 1.0, 0.0],
-            [-sin_theta, 0.0, cos_theta],
-        ]);
-        let reverse_mat = Mat33::new([
-            [cos_theta, 0.0, -sin_theta],
-            [0.0, 1.0, 0.0],
-            [sin_theta, 0.0, cos_theta],
-        ]);
-        let [a, b] = {
-            let bbox = instance.bounding_box();
-            [
-                Vec3::new(bbox[0].0, bbox[1].0, bbox[2].0),
-                Vec3::new(bbox[0].1, bbox[1].1, bbox[2].1),
-            ]
+        RotateY::with_keyframes(instance, Keyframes::constant(angle))
+    }
+
+    // angle 为角度制，关键帧之间对角度线性插值
+    pub fn with_keyframes(instance: G, angle: Keyframes<f64>) -> Self {
+        let angle = angle.map(f64::to_radians);
+        let ranges: Vec<(f64, f64)> = if angle.is_animated() {
+            angle
+                .segments()
+                .map(|(a, b)| (a.min(b), a.max(b)))
+                .collect()
+        } else {
+            angle.values().map(|a| (a, a)).collect()
         };
-        let mut x_interval = Vec2(f64::MAX, f64::MIN);
-        let mut z_interval = x_interval.clone();
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    let x = mix(a.0, b.0, i as f64);
-                    let y = mix(a.1, b.1, j as f64);
-                    let z = mix(a.2, b.2, k as f64);
-                    let point = rot_mat * Point3::new(x, y, z);
-                    x_interval = Vec2::new(x_interval.0.min(point.0), x_interval.1.max(point.1));
-                    z_interval = Vec2::new(z_interval.0.min(point.2), z_interval.1.max(point.2));
-                }
-            }
-        }
+        let bbox = rotated_aabb(instance.bounding_box(), &ranges);
+        let first = angle.values().next().unwrap();
         RotateY {
             instance: Box::new(instance),
-            bbox: AABB::new(
-                Point3::new(x_interval.0, a.1, z_interval.0),
-                Point3::new(x_interval.1, b.1, z_interval.1),
-            ),
-            rot_mat,
-            reverse_mat,
+            bbox,
+            angle,
+            rot_mat: rotation_y(first),
+            reverse_mat: rotation_y(-first),
+        }
+    }
+}
+
+impl<G: Hittable> RotateY<G> {
+    // time 时刻的旋转矩阵及其逆
+    fn matrices(&self, time: f64) -> (Mat33, Mat33) {
+        if self.angle.is_animated() {
+            let angle = self.angle.sample(time, mix);
+            (rotation_y(angle), rotation_y(-angle))
+        } else {
+            (self.rot_mat, self.reverse_mat)
         }
     }
 }
 
 impl<G: Hittable> Hittable for RotateY<G> {
     fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
-        let &Self {
-            rot_mat,
-            reverse_mat,
-            ..
-        } = self;
+        let (rot_mat, reverse_mat) = self.matrices(ray.time);
         let rotated_ray = Ray::new(
             reverse_mat * ray.origin,
             reverse_mat * ray.direction,
@@ -302,48 +692,167 @@ impl<G: Hittable> Hittable for RotateY<G> {
         self.instance.hit(&rotated_ray, t_range).map(|mut rec| {
             rec.p = rot_mat * rec.p;
             rec.normal = rot_mat * rec.normal;
+            rec.tangent = rec.tangent.map(|tangent| rot_mat * tangent);
             rec
         })
     }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let (_, reverse_mat) = self.matrices(ray.time);
+        let rotated_ray = Ray::new(
+            reverse_mat * ray.origin,
+            reverse_mat * ray.direction,
+            ray.time,
+        );
+        self.instance.occluded(&rotated_ray, t_range)
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        let angles: Vec<f64> = self
+            .angle
+            .times_during(shutter)
+            .into_iter()
+            .map(|time| self.angle.sample(time, mix))
+            .collect();
+        let ranges: Vec<(f64, f64)> = angles
+            .windows(2)
+            .map(|pair| (pair[0].min(pair[1]), pair[0].max(pair[1])))
+            .collect();
+        rotated_aabb(&self.instance.bounding_box_during(shutter), &ranges)
+    }
+}
+
+pub struct Transformed<G: Hittable> {
+    instance: Box<G>,
+    transform: AnimatedTransform,
+    bbox: AABB,
+}
+
+// 与 Transformed 相同，但多个实例共享同一个原型，不复制几何数据
+pub type Instance<G> = Transformed<Arc<G>>;
+
+impl<G: Hittable> Transformed<G> {
+    pub fn new(instance: G, transform: Transform) -> Self {
+        Transformed::animated(instance, AnimatedTransform::fixed(transform))
+    }
+
+    pub fn animated(instance: G, transform: AnimatedTransform) -> Self {
+        Transformed {
+            bbox: transform.motion_aabb(instance.bounding_box()),
+            instance: Box::new(instance),
+            transform,
+        }
+    }
+}
+
+// 把局部空间中的交点变换回世界空间
+fn record_to_world<'a>(transform: &Transform, mut rec: HitRecord<'a>) -> HitRecord<'a> {
+    rec.p = transform.transform_point(rec.p);
+    rec.normal = transform.transform_normal(rec.normal);
+    rec.tangent = rec
+        .tangent
+        .map(|tangent| transform.transform_vector(tangent).normalize());
+    rec
+}
+
+impl<G: Hittable> Hittable for Transformed<G> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let transform = self.transform.at(ray.time);
+        let local_ray = transform.inverse_transform_ray(ray);
+        self.instance
+            .hit(&local_ray, t_range)
+            .map(|rec| record_to_world(&transform, rec))
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let local_ray = self.transform.at(ray.time).inverse_transform_ray(ray);
+        self.instance.occluded(&local_ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.transform
+            .motion_aabb_during(&self.instance.bounding_box_during(shutter), shutter)
+    }
+}
+
+impl<G: Solid> Solid for Transformed<G> {
+    fn intervals<'a>(&'a self, ray: &Ray) -> Vec<Interval<'a>> {
+        let transform = self.transform.at(ray.time);
+        let local_ray = transform.inverse_transform_ray(ray);
+        self.instance
+            .intervals(&local_ray)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: record_to_world(&transform, interval.enter),
+                exit: record_to_world(&transform, interval.exit),
+            })
+            .collect()
+    }
+}
+
+// 一组物体共享一个加速结构，可以整体变换或嵌套在其他组中
+pub struct Group {
+    accelerator: AcceleratorEnum,
+}
+
+impl Group {
+    pub fn new(
+        name: Option<String>,
+        children: Vec<Arc<dyn Hittable>>,
+        kind: &AcceleratorKind,
+    ) -> Self {
+        assert!(!children.is_empty(), "Group {name:?} has no children.");
+        Group {
+            accelerator: AcceleratorEnum::new(kind, &children),
+        }
+    }
+}
+
+impl Hittable for Group {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        self.accelerator.hit(ray, t_range)
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.accelerator.occluded(ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        self.accelerator.bounding_box()
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.accelerator.bounding_box_during(shutter)
+    }
 }
 
 pub struct ConstantMedium<G: Hittable, T: Texture> {
     boundary: Box<G>,
     neg_inv_density: f64,
-    phase_function: Isotropic<T>,
+    phase_function: PhaseMaterial<T>,
 }
 
 impl<G: Hittable, T: Texture> ConstantMedium<G, T> {
     pub fn new(boundary: G, density: f64, texture: T) -> Self {
+        ConstantMedium::with_phase(boundary, density, texture, PhaseFunction::Isotropic)
+    }
+
+    pub fn with_phase(boundary: G, density: f64, texture: T, phase: PhaseFunction) -> Self {
         ConstantMedium {
             boundary: Box::new(boundary),
             neg_inv_density: -1.0 / density,
-            phase_function: Isotropic::new(texture),
+            phase_function: PhaseMaterial::new(texture, phase),
         }
     }
 }
 
 impl<G: Hittable + 'static, T: Texture + 'static> Hittable for ConstantMedium<G, T> {
     fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
-        let mut rec1 = if let Some(rec) = self
+        let mut rec1 = self
             .boundary
-            .hit(ray, Vec2::new(f64::NEG_INFINITY, f64::INFINITY))
-        {
-            rec
-        } else {
-            return None;
-        };
-        let mut rec2 = if let Some(rec) = self
+            .hit(ray, Vec2::new(f64::NEG_INFINITY, f64::INFINITY))?;
+        let mut rec2 = self
             .boundary
-            .hit(ray, Vec2::new(rec1.t + 0.00001, f64::INFINITY))
-        {
-            rec
-        } else {
-            return None;
-        };
+            .hit(ray, Vec2::new(rec1.t + 0.00001, f64::INFINITY))?;
         rec1.t = rec1.t.max(t_range.0);
         rec2.t = rec2.t.min(t_range.1);
         if rec1.t > rec2.t {
@@ -362,6 +871,8 @@ impl<G: Hittable + 'static, T: Texture + 'static> Hittable for ConstantMedium<G,
                 material: &self.phase_function,
                 front_face: true,
                 uv: Vec2::zero(),
+                tangent: None,
+                vertex_color: None,
             })
         } else {
             None
@@ -370,4 +881,100 @@ impl<G: Hittable + 'static, T: Texture + 'static> Hittable for ConstantMedium<G,
     fn bounding_box(&self) -> &AABB {
         self.boundary.bounding_box()
     }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.boundary.bounding_box_during(shutter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color, material::Lambertian, random::m_random_range, texture::SolidTexture,
+    };
+
+    // 下半球上的均匀方向，单位球内拒绝采样
+    fn random_downward() -> Vec3 {
+        loop {
+            let p = Vec3::new(
+                m_random_range(-1.0..1.0),
+                m_random_range(-1.0..0.0),
+                m_random_range(-1.0..1.0),
+            );
+            if p.length_squared() <= 1.0 && p.length_squared() > 1e-6 {
+                return p;
+            }
+        }
+    }
+
+    // 两种方式估计形状对 origin 张成的立体角：按 random 采样时 1 / pdf 的均值，
+    // 以及半球上均匀方向命中形状的比例
+    fn check_sampling<S: PlanarShape>(planar: Planar<S, Lambertian<SolidTexture>>, area: f64) {
+        assert!(
+            (planar.area() - area).abs() < 1e-9,
+            "{} != {area}",
+            planar.area()
+        );
+        let origin = Point3::new(0.3, 1.5, 0.2);
+        let count = 200000;
+        let mut sampled = 0.0;
+        for _ in 0..count {
+            let direction = planar.random(origin);
+            let pdf = planar.pdf_value(origin, direction);
+            assert!(
+                pdf > 0.0,
+                "Sampled direction {direction:?} misses the shape."
+            );
+            sampled += 1.0 / pdf;
+        }
+        let sampled = sampled / count as f64;
+        let hits = (0..count)
+            .filter(|_| {
+                let direction = random_downward();
+                planar.pdf_value(origin, direction) > 0.0
+            })
+            .count();
+        let uniform = hits as f64 / count as f64 * 2.0 * PI;
+        assert!(
+            (sampled - uniform).abs() < 0.02 * uniform,
+            "{sampled} != {uniform}"
+        );
+    }
+
+    fn material() -> Arc<Lambertian<SolidTexture>> {
+        Arc::new(Lambertian::new(SolidTexture::new(Color::from_single(0.5))))
+    }
+
+    #[test]
+    fn quad_sampling() {
+        let (u, v) = (Vec3::new(2.0, 0.0, 0.5), Vec3::from_axis_z(-1.5));
+        check_sampling(
+            Quad::new(Point3::new(-1.0, 0.0, 0.5), u, v, material()),
+            3.0,
+        );
+    }
+
+    #[test]
+    fn triangle_sampling() {
+        let (a, b, c) = (
+            Point3::zero(),
+            Point3::from_axis_x(2.0),
+            Point3::from_axis_z(-1.5),
+        );
+        check_sampling(Triangle::new(a, b, c, material()), 1.5);
+    }
+
+    #[test]
+    fn ellipse_and_annulus_sampling() {
+        let (u, v) = (Vec3::from_axis_x(1.5), Vec3::from_axis_z(0.8));
+        let ellipse = EllipseShape { inner_ratio: 0.0 };
+        check_sampling(
+            Planar::with_shape(Point3::zero(), u, v, ellipse, material()),
+            PI * 1.5 * 0.8,
+        );
+        check_sampling(
+            Planar::disk(Point3::zero(), Vec3::from_axis_y(1.0), 1.0, 0.5, material()),
+            PI * 0.75,
+        );
+    }
 }
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    accelerator::Accelerator,
+    hittable::{self, HitRecord, Hittable},
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct GridOptions {
+    // 平均每个图元分到的格子数
+    pub density: f64,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions { density: 3.0 }
+    }
+}
+
+// 每个轴上格子数的上限
+const MAX_RESOLUTION: usize = 128;
+
+// 均匀网格，每个格子记录与其包围盒重叠的图元，用 3D-DDA 由近到远遍历
+pub struct UniformGrid {
+    primitives: Vec<Arc<dyn Hittable>>,
+    options: GridOptions,
+    bbox: AABB,
+    resolution: [usize; 3],
+    cell_size: Vec3,
+    // 第 i 个格子的图元为 cell_items[cell_start[i]..cell_start[i + 1]]
+    cell_start: Vec<usize>,
+    cell_items: Vec<usize>,
+}
+
+impl UniformGrid {
+    pub fn new(list: &[Arc<dyn Hittable>], options: &GridOptions) -> Self {
+        let boxes: Vec<AABB> = list
+            .iter()
+            .map(|item| item.bounding_box().clone())
+            .collect();
+        UniformGrid::build(list.to_vec(), &boxes, options)
+    }
+
+    fn build(primitives: Vec<Arc<dyn Hittable>>, boxes: &[AABB], options: &GridOptions) -> Self {
+        assert!(!primitives.is_empty(), "Grid has no primitives.");
+        let bbox = boxes
+            .iter()
+            .cloned()
+            .reduce(|a, b| AABB::from_aabb(&a, &b))
+            .unwrap();
+        let extent = [0, 1, 2].map(|axis| bbox[axis].1 - bbox[axis].0);
+        // 按体积分配格子，使格子接近立方体
+        let volume = extent[0] * extent[1] * extent[2];
+        let cells_per_unit = (options.density * primitives.len() as f64 / volume).cbrt();
+        let resolution =
+            extent.map(|e| ((e * cells_per_unit).round() as usize).clamp(1, MAX_RESOLUTION));
+        let cell_size = Vec3::new(
+            extent[0] / resolution[0] as f64,
+            extent[1] / resolution[1] as f64,
+            extent[2] / resolution[2] as f64,
+        );
+        let mut grid = UniformGrid {
+            primitives,
+            options: *options,
+            bbox,
+            resolution,
+            cell_size,
+            cell_start: vec![],
+            cell_items: vec![],
+        };
+
+        // 先统计每个格子的图元数，再按前缀和填入
+        let ranges: Vec<[[usize; 3]; 2]> = boxes
+            .iter()
+            .map(|b| {
+                [
+                    grid.cell_of(Point3::new(b[0].0, b[1].0, b[2].0)),
+                    grid.cell_of(Point3::new(b[0].1, b[1].1, b[2].1)),
+                ]
+            })
+            .collect();
+        let cell_count = resolution[0] * resolution[1] * resolution[2];
+        let mut counts = vec![0; cell_count + 1];
+        for_each_cell(&ranges, |cell, _| counts[grid.index(cell) + 1] += 1);
+        for i in 0..cell_count {
+            counts[i + 1] += counts[i];
+        }
+        let mut next = counts.clone();
+        let mut cell_items = vec![0; counts[cell_count]];
+        for_each_cell(&ranges, |cell, item| {
+            let index = grid.index(cell);
+            cell_items[next[index]] = item;
+            next[index] += 1;
+        });
+        grid.cell_start = counts;
+        grid.cell_items = cell_items;
+        grid
+    }
+
+    fn cell_of(&self, p: Point3) -> [usize; 3] {
+        [0, 1, 2].map(|axis| {
+            let v = ((p[axis] - self.bbox[axis].0) / self.cell_size[axis]).floor();
+            (v.max(0.0) as usize).min(self.resolution[axis] - 1)
+        })
+    }
+
+    fn index(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.resolution[1] + cell[1]) * self.resolution[0] + cell[0]
+    }
+
+    // 由近到远遍历光线经过的格子，visit 的参数为格子中的图元和光线离开该格子的参数
+    // visit 返回 true 时结束遍历
+    fn traverse(&self, ray: &Ray, t_range: Vec2, mut visit: impl FnMut(&[usize], f64) -> bool) {
+        let Some(range) = self.bbox.clip(ray, t_range) else {
+            return;
+        };
+        let d = ray.direction;
+        let mut cell = self.cell_of(ray.at(range.0));
+        let step = [0, 1, 2].map(|axis| if d[axis] > 0.0 { 1 } else { -1 });
+        let t_delta = [0, 1, 2].map(|axis| (self.cell_size[axis] / d[axis]).abs());
+        let mut t_max = [0, 1, 2].map(|axis| {
+            if d[axis] == 0.0 {
+                return f64::INFINITY;
+            }
+            let boundary = cell[axis] + if step[axis] > 0 { 1 } else { 0 };
+            (self.bbox[axis].0 + boundary as f64 * self.cell_size[axis] - ray.origin[axis])
+                / d[axis]
+        });
+        loop {
+            let index = self.index(cell);
+            let items = &self.cell_items[self.cell_start[index]..self.cell_start[index + 1]];
+            let axis = (0..3)
+                .min_by(|&a, &b| t_max[a].total_cmp(&t_max[b]))
+                .unwrap();
+            if visit(items, t_max[axis]) || t_max[axis] > range.1 {
+                return;
+            }
+            let next = cell[axis] as i64 + step[axis];
+            if next < 0 || next as usize >= self.resolution[axis] {
+                return;
+            }
+            cell[axis] = next as usize;
+            t_max[axis] += t_delta[axis];
+        }
+    }
+}
+
+// 对每个图元覆盖的每个格子调用 f(格子, 图元下标)
+fn for_each_cell(ranges: &[[[usize; 3]; 2]], mut f: impl FnMut([usize; 3], usize)) {
+    for (item, [lower, upper]) in ranges.iter().enumerate() {
+        for z in lower[2]..=upper[2] {
+            for y in lower[1]..=upper[1] {
+                for x in lower[0]..=upper[0] {
+                    f([x, y, z], item);
+                }
+            }
+        }
+    }
+}
+
+impl Hittable for UniformGrid {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let mut result = None;
+        let mut closest_so_far = t_range.1;
+        self.traverse(ray, t_range, |items, t_exit| {
+            for &item in items {
+                let range = Vec2::new(t_range.0, closest_so_far);
+                if let Some(rec) = self.primitives[item].hit(ray, range) {
+                    closest_so_far = rec.t;
+                    result = Some(rec);
+                }
+            }
+            // 图元可能跨越多个格子，交点不在当前格子内时后面的格子可能有更近的交点
+            closest_so_far <= t_exit
+        });
+        result
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let mut occluded = false;
+        self.traverse(ray, t_range, |items, _| {
+            occluded = items
+                .iter()
+                .any(|&item| self.primitives[item].occluded(ray, t_range));
+            occluded
+        });
+        occluded
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        hittable::bounding_box_during(&self.primitives, shutter)
+    }
+}
+
+impl Accelerator for UniformGrid {
+    // 网格无法 refit，每帧按快门区间内的包围盒重新构建
+    fn update(&mut self, shutter: Vec2) -> bool {
+        let boxes: Vec<AABB> = self
+            .primitives
+            .iter()
+            .map(|item| item.bounding_box_during(shutter))
+            .collect();
+        *self = UniformGrid::build(std::mem::take(&mut self.primitives), &boxes, &self.options);
+        true
+    }
+}
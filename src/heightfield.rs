@@ -0,0 +1,381 @@
+use std::{path::Path, sync::Arc};
+
+use image::ImageReader;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    mesh::moller_trumbore,
+    noise::PerlinNoise,
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// 规则网格上的高度采样，取值范围 [0, 1]，按行存储（每行 width 个）
+pub struct HeightMap {
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+}
+
+impl HeightMap {
+    pub fn new(width: usize, depth: usize, heights: Vec<f64>) -> Self {
+        assert!(
+            width >= 2 && depth >= 2,
+            "Height map needs at least 2x2 samples."
+        );
+        assert_eq!(heights.len(), width * depth, "Height map size mismatch.");
+        HeightMap {
+            width,
+            depth,
+            heights,
+        }
+    }
+
+    // 灰度图的亮度作为高度，resolution 为 None 时使用图片分辨率，否则双线性重采样
+    pub fn from_image(path: impl AsRef<Path>, resolution: Option<(usize, usize)>) -> Self {
+        let image = ImageReader::open(path)
+            .expect("Failed to open height map image.")
+            .decode()
+            .expect("Failed to decode height map image.")
+            .to_luma16();
+        let (image_width, image_depth) = (image.width() as usize, image.height() as usize);
+        let pixel = |x: usize, z: usize| image.get_pixel(x as u32, z as u32).0[0] as f64 / 65535.0;
+        let (width, depth) = resolution.unwrap_or((image_width, image_depth));
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let fx = x as f64 / (width - 1) as f64 * (image_width - 1) as f64;
+                let fz = z as f64 / (depth - 1) as f64 * (image_depth - 1) as f64;
+                let (x0, z0) = (fx.floor() as usize, fz.floor() as usize);
+                let (x1, z1) = ((x0 + 1).min(image_width - 1), (z0 + 1).min(image_depth - 1));
+                let (tx, tz) = (fx - x0 as f64, fz - z0 as f64);
+                let top = pixel(x0, z0) * (1.0 - tx) + pixel(x1, z0) * tx;
+                let bottom = pixel(x0, z1) * (1.0 - tx) + pixel(x1, z1) * tx;
+                top * (1.0 - tz) + bottom * tz
+            })
+            .collect();
+        HeightMap::new(width, depth, heights)
+    }
+
+    // frequency 为整个高度图范围内的噪声周期数，octaves 为 fBm 的层数
+    pub fn from_noise(resolution: (usize, usize), frequency: f64, octaves: usize) -> Self {
+        let noise = PerlinNoise::new();
+        let (width, depth) = resolution;
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let p = Point3::new(
+                    x as f64 / (width - 1) as f64 * frequency,
+                    0.0,
+                    z as f64 / (depth - 1) as f64 * frequency,
+                );
+                (noise.turb(p, octaves) + 1.0) / 2.0
+            })
+            .collect();
+        HeightMap::new(width, depth, heights)
+    }
+}
+
+// 高度的 min/max 金字塔中的一层，每个元素覆盖 2^level 个格子
+struct MipLevel {
+    width: usize,
+    depth: usize,
+    bounds: Vec<Vec2>,
+}
+
+// 以 origin 为最小角、在 XZ 平面上铺开的高度场
+pub struct Heightfield<M: Material> {
+    origin: Point3,
+    cell_size: Vec2,
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    mips: Vec<MipLevel>,
+    material: Arc<M>,
+    bbox: AABB,
+}
+
+impl<M: Material> Heightfield<M> {
+    pub fn new(
+        map: HeightMap,
+        origin: Point3,
+        extent: Vec2,
+        height_scale: f64,
+        material: Arc<M>,
+    ) -> Self {
+        let HeightMap {
+            width,
+            depth,
+            heights,
+        } = map;
+        let heights: Vec<f64> = heights
+            .into_iter()
+            .map(|h| origin.1 + h * height_scale)
+            .collect();
+        let cell_size = Vec2::new(extent.0 / (width - 1) as f64, extent.1 / (depth - 1) as f64);
+        let height = |x: usize, z: usize| heights[z * width + x];
+
+        // 中心差分求顶点法线
+        let normals = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+                let dx = (height(x1, z) - height(x0, z)) / ((x1 - x0) as f64 * cell_size.0);
+                let dz = (height(x, z1) - height(x, z0)) / ((z1 - z0) as f64 * cell_size.1);
+                Vec3::new(-dx, 1.0, -dz).normalize()
+            })
+            .collect();
+
+        // 第 0 层为每个格子四个顶点的高度范围，之后每层合并 2x2
+        let mut mips = vec![MipLevel {
+            width: width - 1,
+            depth: depth - 1,
+            bounds: (0..depth - 1)
+                .flat_map(|z| (0..width - 1).map(move |x| (x, z)))
+                .map(|(x, z)| {
+                    let corners = [
+                        height(x, z),
+                        height(x + 1, z),
+                        height(x, z + 1),
+                        height(x + 1, z + 1),
+                    ];
+                    Vec2::new(
+                        corners.into_iter().fold(f64::INFINITY, f64::min),
+                        corners.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                    )
+                })
+                .collect(),
+        }];
+        while let Some(last) = mips.last()
+            && (last.width > 1 || last.depth > 1)
+        {
+            let (w, d) = (last.width.div_ceil(2), last.depth.div_ceil(2));
+            let bounds = (0..d)
+                .flat_map(|z| (0..w).map(move |x| (x, z)))
+                .map(|(x, z)| {
+                    let mut bound = Vec2::new(f64::INFINITY, f64::NEG_INFINITY);
+                    for (cx, cz) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (cx, cz) = (2 * x + cx, 2 * z + cz);
+                        if cx < last.width && cz < last.depth {
+                            let child = last.bounds[cz * last.width + cx];
+                            bound = Vec2::new(bound.0.min(child.0), bound.1.max(child.1));
+                        }
+                    }
+                    bound
+                })
+                .collect();
+            mips.push(MipLevel {
+                width: w,
+                depth: d,
+                bounds,
+            });
+        }
+
+        let root = mips.last().unwrap().bounds[0];
+        let bbox = AABB::new(
+            Point3::new(origin.0, root.0, origin.2),
+            Point3::new(origin.0 + extent.0, root.1, origin.2 + extent.1),
+        );
+        Heightfield {
+            origin,
+            cell_size,
+            width,
+            depth,
+            heights,
+            normals,
+            mips,
+            material,
+            bbox,
+        }
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Point3 {
+        Point3::new(
+            self.origin.0 + x as f64 * self.cell_size.0,
+            self.heights[z * self.width + x],
+            self.origin.2 + z as f64 * self.cell_size.1,
+        )
+    }
+
+    // 某一层中 (x, z) 元素在世界空间中的包围盒
+    fn block_box(&self, level: usize, x: usize, z: usize) -> AABB {
+        let mip = &self.mips[level];
+        let span = (1 << level) as f64;
+        let bound = mip.bounds[z * mip.width + x];
+        let min = Point3::new(
+            self.origin.0 + x as f64 * span * self.cell_size.0,
+            bound.0,
+            self.origin.2 + z as f64 * span * self.cell_size.1,
+        );
+        let max = Point3::new(
+            (min.0 + span * self.cell_size.0).min(self.bbox[0].1),
+            bound.1,
+            (min.2 + span * self.cell_size.1).min(self.bbox[2].1),
+        );
+        AABB::new(min, max)
+    }
+
+    // 光线离开包围盒在 XZ 平面上投影的参数
+    fn footprint_exit(ray: &Ray, bbox: &AABB) -> f64 {
+        [0, 2]
+            .into_iter()
+            .map(|axis| {
+                let d = ray.direction[axis];
+                if d > 0.0 {
+                    (bbox[axis].1 - ray.origin[axis]) / d
+                } else if d < 0.0 {
+                    (bbox[axis].0 - ray.origin[axis]) / d
+                } else {
+                    f64::INFINITY
+                }
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn hit_cell<'a>(
+        &'a self,
+        ray: &Ray,
+        x: usize,
+        z: usize,
+        t_range: Vec2,
+    ) -> Option<HitRecord<'a>> {
+        let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+        let triangles = [
+            [corners[0], corners[1], corners[2]],
+            [corners[0], corners[2], corners[3]],
+        ];
+        let mut closest: Option<(f64, f64, f64, usize)> = None;
+        for (i, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|(x, z)| self.vertex(x, z));
+            let t_max = closest.map_or(t_range.1, |hit| hit.0);
+            if let Some((t, u, v)) =
+                moller_trumbore(ray, a, b - a, c - a, Vec2::new(t_range.0, t_max))
+            {
+                closest = Some((t, u, v, i));
+            }
+        }
+        let (t, u, v, i) = closest?;
+        let triangle = triangles[i];
+        let [n0, n1, n2] = triangle.map(|(x, z)| self.normals[z * self.width + x]);
+        let normal = ((1.0 - u - v) * n0 + u * n1 + v * n2).normalize();
+        let front_face = ray.direction.dot(normal) < 0.0;
+        let p = ray.at(t);
+        Some(HitRecord {
+            p,
+            normal: if front_face { normal } else { -normal },
+            t,
+            material: self.material.as_ref(),
+            front_face,
+            uv: Vec2::new(
+                (p.0 - self.origin.0) / (self.bbox[0].1 - self.bbox[0].0),
+                (p.2 - self.origin.2) / (self.bbox[2].1 - self.bbox[2].0),
+            ),
+            tangent: None,
+            vertex_color: None,
+        })
+    }
+}
+
+impl<M: Material + 'static> Hittable for Heightfield<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let range = self.bbox.clip(ray, t_range)?;
+        let cells = (self.width - 1, self.depth - 1);
+        let mut t = range.0;
+        // 在 XZ 平面上做 DDA，每一步从金字塔顶层向下找第一个被光线错过的块并整块跳过
+        while t <= range.1 {
+            let p = ray.at(t);
+            let cell = |v: f64, origin: f64, size: f64, count: usize| {
+                (((v - origin) / size).floor().max(0.0) as usize).min(count - 1)
+            };
+            let x = cell(p.0, self.origin.0, self.cell_size.0, cells.0);
+            let z = cell(p.2, self.origin.2, self.cell_size.1, cells.1);
+            let mut skip = None;
+            for level in (0..self.mips.len()).rev() {
+                let block = self.block_box(level, x >> level, z >> level);
+                if !block.hit(ray, Vec2::new(t, range.1)) {
+                    skip = Some(block);
+                    break;
+                }
+            }
+            let block = match skip {
+                Some(block) => block,
+                None => {
+                    if let Some(rec) =
+                        self.hit_cell(ray, x, z, Vec2::new(t_range.0.max(t), range.1))
+                    {
+                        return Some(rec);
+                    }
+                    self.block_box(0, x, z)
+                }
+            };
+            // 稍微越过块的边界，保证下一步进入相邻的格子
+            let exit = Self::footprint_exit(ray, &block);
+            let nudge = 1e-7 * (self.cell_size.0 + self.cell_size.1) / ray.direction.length();
+            t = exit.max(t) + nudge;
+        }
+        None
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, random::m_random, texture::SolidTexture};
+
+    #[test]
+    fn mip_dda_matches_brute_force() {
+        let (width, depth) = (23, 17);
+        let heights = (0..width * depth).map(|_| m_random::<f64>()).collect();
+        let material = Arc::new(Lambertian::new(SolidTexture::new(Vec3::from_single(0.5))));
+        let heightfield = Heightfield::new(
+            HeightMap::new(width, depth, heights),
+            Point3::new(-2.0, 0.5, -1.0),
+            Vec2::new(4.0, 3.0),
+            1.5,
+            material,
+        );
+        let t_range = Vec2::new(0.001, f64::INFINITY);
+        // 逐格子对两个三角形求交
+        let brute_force = |ray: &Ray| {
+            (0..depth - 1)
+                .flat_map(|z| (0..width - 1).map(move |x| (x, z)))
+                .flat_map(|(x, z)| {
+                    let [a, b, c, d] = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)]
+                        .map(|(x, z)| heightfield.vertex(x, z));
+                    [(a, b, c), (a, c, d)]
+                })
+                .filter_map(|(a, b, c)| moller_trumbore(ray, a, b - a, c - a, t_range))
+                .map(|(t, _, _)| t)
+                .min_by(f64::total_cmp)
+        };
+        let bbox = heightfield.bounding_box().clone();
+        let random_in_bbox = |margin: f64| {
+            let axis = |i: usize| {
+                let Vec2(lo, hi) = bbox[i];
+                lo - margin + m_random::<f64>() * (hi - lo + 2.0 * margin)
+            };
+            Point3::new(axis(0), axis(1), axis(2))
+        };
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let origin = random_in_bbox(2.0);
+            let ray = Ray::new(origin, random_in_bbox(0.0) - origin, 0.0);
+            let expected = brute_force(&ray);
+            let actual = heightfield.hit(&ray, t_range).map(|rec| rec.t);
+            match (actual, expected) {
+                (Some(a), Some(e)) => assert!((a - e).abs() < 1e-9, "{a} != {e}"),
+                (None, None) => {}
+                _ => panic!("{actual:?} != {expected:?} for {ray:?}"),
+            }
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 1000);
+    }
+}
@@ -2,6 +2,7 @@ use std::{ops::Index, sync::Arc};
 
 use crate::{
     aabb::AABB,
+    color::Color,
     material::Material,
     ray::Ray,
     vec::{Point3, Vec2, Vec3},
@@ -14,11 +15,48 @@ pub struct HitRecord<'mat> {
     pub material: &'mat dyn Material,
     pub front_face: bool,
     pub uv: Vec2,
+    // 曲线等一维图元沿长度方向的单位切线
+    pub tangent: Option<Vec3>,
+    // 网格顶点颜色插值的结果，没有顶点颜色时为 None
+    pub vertex_color: Option<Color>,
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>>;
+    // 只判断 t_range 内是否有交点，找到任意一个交点即可返回
+    // 相机的环境光遮蔽模式用它测试遮挡光线
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.hit(ray, t_range).is_some()
+    }
     fn bounding_box(&self) -> &AABB;
+    // 快门区间 shutter 内的包围盒，只有随时间运动的物体需要给出比 bounding_box 更紧的结果
+    fn bounding_box_during(&self, _shutter: Vec2) -> AABB {
+        self.bounding_box().clone()
+    }
+}
+
+// 共享的物体，例如多个实例引用的同一个原型
+impl<G: Hittable + ?Sized> Hittable for Arc<G> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        self.as_ref().hit(ray, t_range)
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.as_ref().occluded(ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        self.as_ref().bounding_box()
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.as_ref().bounding_box_during(shutter)
+    }
+}
+
+// 一组物体在快门区间内包围盒的并集，list 不能为空
+pub fn bounding_box_during(list: &[Arc<dyn Hittable>], shutter: Vec2) -> AABB {
+    list.iter()
+        .map(|item| item.bounding_box_during(shutter))
+        .reduce(|a, b| AABB::from_aabb(&a, &b))
+        .unwrap()
 }
 
 pub struct HittableList {
@@ -44,13 +82,23 @@ impl HittableList {
         self.list.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
     pub fn iter(&self) -> std::slice::Iter<'_, Arc<dyn Hittable>> {
         self.list.iter()
     }
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_range: Vec2) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'_>> {
         let mut result: Option<HitRecord> = None;
         let mut closest_so_far = t_range.1;
         for item in self.list.iter() {
@@ -61,6 +109,9 @@ impl Hittable for HittableList {
         }
         result
     }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.list.iter().any(|item| item.occluded(ray, t_range))
+    }
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
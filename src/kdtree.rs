@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    accelerator::Accelerator,
+    hittable::{self, HitRecord, Hittable},
+    ray::Ray,
+    vec::{Point3, Vec2},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct KdTreeOptions {
+    // 求交一个图元的代价，相对于遍历一个节点
+    pub intersect_cost: f64,
+    pub traversal_cost: f64,
+    // 划分出空子节点时代价的折扣
+    pub empty_bonus: f64,
+    pub max_leaf_size: usize,
+    // 为 None 时取 8 + 1.3 log2(n)
+    pub max_depth: Option<usize>,
+}
+
+impl Default for KdTreeOptions {
+    fn default() -> Self {
+        KdTreeOptions {
+            intersect_cost: 80.0,
+            traversal_cost: 1.0,
+            empty_bonus: 0.5,
+            max_leaf_size: 1,
+            max_depth: None,
+        }
+    }
+}
+
+// 遍历栈的大小，也是树深度的上限
+const STACK_SIZE: usize = 64;
+// 连续多少次划分代价不降低就停止
+const MAX_BAD_REFINES: usize = 3;
+
+// 扁平数组中的节点，内部节点的下方孩子紧跟在它后面
+enum KdNode {
+    // 图元下标在 items 中的区间，跨越划分平面的图元会出现在多个叶子中
+    Leaf {
+        start: usize,
+        count: usize,
+    },
+    // above 为划分平面上方孩子的下标
+    Interior {
+        axis: usize,
+        split: f64,
+        above: usize,
+    },
+}
+
+// 用 SAH 选择划分平面的 kd 树，按光线穿过各节点的参数区间由近到远遍历
+pub struct KdTree {
+    primitives: Vec<Arc<dyn Hittable>>,
+    options: KdTreeOptions,
+    bbox: AABB,
+    nodes: Vec<KdNode>,
+    items: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(list: &[Arc<dyn Hittable>], options: &KdTreeOptions) -> Self {
+        let boxes: Vec<AABB> = list
+            .iter()
+            .map(|item| item.bounding_box().clone())
+            .collect();
+        KdTree::build(list.to_vec(), &boxes, options)
+    }
+
+    fn build(primitives: Vec<Arc<dyn Hittable>>, boxes: &[AABB], options: &KdTreeOptions) -> Self {
+        assert!(!primitives.is_empty(), "KdTree has no primitives.");
+        let bbox = boxes
+            .iter()
+            .cloned()
+            .reduce(|a, b| AABB::from_aabb(&a, &b))
+            .unwrap();
+        let max_depth = options
+            .max_depth
+            .unwrap_or((8.0 + 1.3 * (boxes.len() as f64).log2()).round() as usize)
+            .min(STACK_SIZE - 1);
+        let mut builder = Builder {
+            boxes,
+            options,
+            nodes: vec![],
+            items: vec![],
+        };
+        builder.build(&bbox, (0..boxes.len()).collect(), max_depth, 0);
+        KdTree {
+            primitives,
+            options: *options,
+            bbox,
+            nodes: builder.nodes,
+            items: builder.items,
+        }
+    }
+}
+
+struct Builder<'a> {
+    boxes: &'a [AABB],
+    options: &'a KdTreeOptions,
+    nodes: Vec<KdNode>,
+    items: Vec<usize>,
+}
+
+impl Builder<'_> {
+    fn leaf(&mut self, items: &[usize]) {
+        self.nodes.push(KdNode::Leaf {
+            start: self.items.len(),
+            count: items.len(),
+        });
+        self.items.extend_from_slice(items);
+    }
+
+    fn build(&mut self, bounds: &AABB, items: Vec<usize>, depth: usize, bad_refines: usize) {
+        let n = items.len();
+        if n <= self.options.max_leaf_size || depth == 0 {
+            return self.leaf(&items);
+        }
+        let leaf_cost = self.options.intersect_cost * n as f64;
+        let Some((axis, split, cost)) = self.find_split(bounds, &items) else {
+            return self.leaf(&items);
+        };
+        let bad_refines = bad_refines + usize::from(cost > leaf_cost);
+        if (cost > 4.0 * leaf_cost && n < 16) || bad_refines == MAX_BAD_REFINES {
+            return self.leaf(&items);
+        }
+
+        // 落在平面上的扁平图元两边都放，避免被丢掉
+        let (mut below, mut above) = (vec![], vec![]);
+        for &item in &items {
+            let Vec2(lo, hi) = self.boxes[item][axis];
+            if lo < split || hi <= split {
+                below.push(item);
+            }
+            if hi > split || lo >= split {
+                above.push(item);
+            }
+        }
+        drop(items);
+        let index = self.nodes.len();
+        self.nodes.push(KdNode::Leaf { start: 0, count: 0 });
+        self.build(
+            &with_interval(bounds, axis, (bounds[axis].0, split)),
+            below,
+            depth - 1,
+            bad_refines,
+        );
+        let above_index = self.nodes.len();
+        self.build(
+            &with_interval(bounds, axis, (split, bounds[axis].1)),
+            above,
+            depth - 1,
+            bad_refines,
+        );
+        self.nodes[index] = KdNode::Interior {
+            axis,
+            split,
+            above: above_index,
+        };
+    }
+
+    // 从最长轴开始扫描图元包围盒的边界，返回 SAH 代价最小的 (轴, 平面, 代价)
+    fn find_split(&self, bounds: &AABB, items: &[usize]) -> Option<(usize, f64, f64)> {
+        let options = self.options;
+        let extent = [0, 1, 2].map(|axis| bounds[axis].1 - bounds[axis].0);
+        let inv_area = 1.0 / bounds.surface_area();
+        let longest = (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap();
+        let mut best: Option<(usize, f64, f64)> = None;
+        for retry in 0..3 {
+            let axis = (longest + retry) % 3;
+            let (other0, other1) = ((axis + 1) % 3, (axis + 2) % 3);
+            // (位置, 是否为结束边)，位置相同时开始边在前
+            let mut edges: Vec<(f64, bool)> = items
+                .iter()
+                .flat_map(|&item| {
+                    let Vec2(lo, hi) = self.boxes[item][axis];
+                    [(lo, false), (hi, true)]
+                })
+                .collect();
+            edges.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+            let (mut below, mut above) = (0, items.len());
+            for &(t, end) in &edges {
+                if end {
+                    above -= 1;
+                }
+                if t > bounds[axis].0 && t < bounds[axis].1 {
+                    let below_extent = t - bounds[axis].0;
+                    let above_extent = bounds[axis].1 - t;
+                    let cross = extent[other0] * extent[other1];
+                    let perimeter = extent[other0] + extent[other1];
+                    let below_area = 2.0 * (cross + below_extent * perimeter);
+                    let above_area = 2.0 * (cross + above_extent * perimeter);
+                    let bonus = if below == 0 || above == 0 {
+                        options.empty_bonus
+                    } else {
+                        0.0
+                    };
+                    let cost = options.traversal_cost
+                        + options.intersect_cost
+                            * (1.0 - bonus)
+                            * (below_area * inv_area * below as f64
+                                + above_area * inv_area * above as f64);
+                    if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                        best = Some((axis, t, cost));
+                    }
+                }
+                if !end {
+                    below += 1;
+                }
+            }
+            if best.is_some() {
+                break;
+            }
+        }
+        best
+    }
+}
+
+fn with_interval(bounds: &AABB, axis: usize, interval: (f64, f64)) -> AABB {
+    let mut lo = [bounds[0].0, bounds[1].0, bounds[2].0];
+    let mut hi = [bounds[0].1, bounds[1].1, bounds[2].1];
+    lo[axis] = interval.0;
+    hi[axis] = interval.1;
+    AABB::new(
+        Point3::new(lo[0], lo[1], lo[2]),
+        Point3::new(hi[0], hi[1], hi[2]),
+    )
+}
+
+impl KdTree {
+    // 由近到远访问光线经过的叶子，visit 的参数为叶子中的图元和光线在叶子内的参数区间
+    // visit 返回 true 时结束遍历
+    fn traverse(&self, ray: &Ray, t_range: Vec2, mut visit: impl FnMut(&[usize], Vec2) -> bool) {
+        let Some(range) = self.bbox.clip(ray, t_range) else {
+            return;
+        };
+        let mut stack = [(0, 0.0, 0.0); STACK_SIZE];
+        let mut top = 0;
+        let (mut current, mut t_min, mut t_max) = (0, range.0, range.1);
+        loop {
+            match self.nodes[current] {
+                KdNode::Interior { axis, split, above } => {
+                    let origin = ray.origin[axis];
+                    let t_plane = (split - origin) / ray.direction[axis];
+                    let below_first =
+                        origin < split || (origin == split && ray.direction[axis] <= 0.0);
+                    let (first, second) = if below_first {
+                        (current + 1, above)
+                    } else {
+                        (above, current + 1)
+                    };
+                    if t_plane.is_nan() || t_plane > t_max || t_plane <= 0.0 {
+                        current = first;
+                    } else if t_plane < t_min {
+                        current = second;
+                    } else {
+                        stack[top] = (second, t_plane, t_max);
+                        top += 1;
+                        current = first;
+                        t_max = t_plane;
+                    }
+                    continue;
+                }
+                KdNode::Leaf { start, count } => {
+                    if visit(&self.items[start..start + count], Vec2::new(t_min, t_max)) {
+                        return;
+                    }
+                }
+            }
+            if top == 0 {
+                return;
+            }
+            top -= 1;
+            (current, t_min, t_max) = stack[top];
+        }
+    }
+}
+
+impl Hittable for KdTree {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let mut result = None;
+        let mut closest_so_far = t_range.1;
+        self.traverse(ray, t_range, |items, range| {
+            for &item in items {
+                let range = Vec2::new(t_range.0, closest_so_far);
+                if let Some(rec) = self.primitives[item].hit(ray, range) {
+                    closest_so_far = rec.t;
+                    result = Some(rec);
+                }
+            }
+            // 交点可能在叶子之外，只有落在当前叶子内才能确定最近
+            closest_so_far <= range.1
+        });
+        result
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        let mut occluded = false;
+        self.traverse(ray, t_range, |items, _| {
+            occluded = items
+                .iter()
+                .any(|&item| self.primitives[item].occluded(ray, t_range));
+            occluded
+        });
+        occluded
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        hittable::bounding_box_during(&self.primitives, shutter)
+    }
+}
+
+impl Accelerator for KdTree {
+    // kd 树的划分平面依赖图元位置，每帧重新构建
+    fn update(&mut self, shutter: Vec2) -> bool {
+        let boxes: Vec<AABB> = self
+            .primitives
+            .iter()
+            .map(|item| item.bounding_box_during(shutter))
+            .collect();
+        *self = KdTree::build(std::mem::take(&mut self.primitives), &boxes, &self.options);
+        true
+    }
+}
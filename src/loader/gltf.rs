@@ -0,0 +1,227 @@
+use std::{collections::HashMap, sync::Arc};
+
+use gltf::{Semantic, camera::Projection, image::Format, material::AlphaMode, mesh::Mode};
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+use crate::{
+    bvh::BvhOptions,
+    cache::{self, MeshCache, SourceCache, load_mesh},
+    camera::CameraBuilder,
+    color::Color,
+    hittable::HittableList,
+    material::{Dielectric, DiffuseLight, Lambertian, MaterialEnum, Metal},
+    matrix::Mat44,
+    mesh::{Mesh, MeshData},
+    texture::{ImageTexture, SolidTexture, TextureEnum},
+    vec::{Point3, Vec2, Vec3},
+};
+
+pub struct GltfScene {
+    pub world: HittableList,
+    // 场景中第一个透视相机，没有相机时为 None
+    pub camera: Option<CameraBuilder>,
+}
+
+// glTF 的节点矩阵按列存储，转置后即为行主序的 Mat44
+fn node_matrix(node: &gltf::Node) -> Mat44 {
+    let columns = node.transform().matrix();
+    Mat44::new(std::array::from_fn(|row| {
+        std::array::from_fn(|col| columns[col][row] as f64)
+    }))
+}
+
+fn build_image(data: &gltf::image::Data) -> Option<DynamicImage> {
+    let (width, height) = (data.width, data.height);
+    match data.format {
+        Format::R8G8B8 => RgbImage::from_raw(width, height, data.pixels.clone()).map(Into::into),
+        Format::R8G8B8A8 => RgbaImage::from_raw(width, height, data.pixels.clone()).map(Into::into),
+        _ => None,
+    }
+}
+
+fn build_material(material: &gltf::Material, images: &[gltf::image::Data]) -> MaterialEnum {
+    let pbr = material.pbr_metallic_roughness();
+    let emissive = material.emissive_factor().map(|c| c as f64);
+    let emissive = Color::new(emissive[0], emissive[1], emissive[2]);
+    if emissive.0.max(emissive.1).max(emissive.2) > 0.0 {
+        let strength = material.emissive_strength().unwrap_or(1.0) as f64;
+        return MaterialEnum::DiffuseLight(DiffuseLight::new(emissive, strength));
+    }
+    let eta = material.ior().unwrap_or(1.5) as f64;
+    let transmission = material
+        .transmission()
+        .map_or(0.0, |t| t.transmission_factor());
+    let [r, g, b, alpha] = pbr.base_color_factor().map(|c| c as f64);
+    if transmission > 0.5 || (material.alpha_mode() == AlphaMode::Blend && alpha < 1.0) {
+        return MaterialEnum::Dielectric(Dielectric::new(eta));
+    }
+    let texture = pbr
+        .base_color_texture()
+        .and_then(|info| build_image(&images[info.texture().source().index()]))
+        .map_or_else(
+            || TextureEnum::SolidTexture(SolidTexture::new(Color::new(r, g, b))),
+            |image| TextureEnum::ImageTexture(ImageTexture::from_image(image)),
+        );
+    if pbr.metallic_factor() >= 0.5 {
+        MaterialEnum::Metal(Metal::new(texture, pbr.roughness_factor() as f64))
+    } else {
+        MaterialEnum::Lambertian(Lambertian::new(texture))
+    }
+}
+
+struct SceneLoader<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    // 外部 .bin 文件不在源文件中，把所有缓冲的哈希计入缓存的键
+    buffers_hash: u64,
+    images: &'a [gltf::image::Data],
+    materials: HashMap<Option<usize>, Arc<MaterialEnum>>,
+    bvh: &'a BvhOptions,
+    cache: Option<SourceCache<'a>>,
+    world: HittableList,
+    camera: Option<CameraBuilder>,
+}
+
+impl SceneLoader<'_> {
+    fn visit(&mut self, node: gltf::Node, parent: &Mat44) {
+        let world = *parent * node_matrix(&node);
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.load_primitive(&node, &primitive, &world);
+            }
+        }
+        if let (None, Some(camera)) = (&self.camera, node.camera())
+            && let Projection::Perspective(perspective) = camera.projection()
+        {
+            // glTF 相机朝向 -Z，上方为 +Y
+            let mut builder = CameraBuilder::new()
+                .look_from(world.transform_point(Point3::zero()))
+                .look_at(world.transform_point(Point3::from_axis_z(-1.0)))
+                .view_up(world.transform_vector(Vec3::from_axis_y(1.0)))
+                .vertical_fov((perspective.yfov() as f64).to_degrees());
+            if let Some(ratio) = perspective.aspect_ratio() {
+                builder = builder.aspect_ratio(ratio as f64);
+            }
+            if let Some(zfar) = perspective.zfar() {
+                builder = builder.max_ray_range(zfar as f64);
+            }
+            self.camera = Some(builder);
+        }
+        for child in node.children() {
+            self.visit(child, &world);
+        }
+    }
+
+    fn load_primitive(&mut self, node: &gltf::Node, primitive: &gltf::Primitive, world: &Mat44) {
+        if primitive.mode() != Mode::Triangles {
+            println!("跳过非三角形图元 {:?}。", primitive.mode());
+            return;
+        }
+        // 缩放为 0 的节点不可见，直接跳过
+        let Some(inverse) = world.inverse() else {
+            return;
+        };
+        let Some(position_count) = primitive.get(&Semantic::Positions).map(|a| a.count()) else {
+            return;
+        };
+        if primitive.indices().map_or(position_count, |a| a.count()) < 3 {
+            return;
+        }
+        // 顶点已经变换到世界空间，同一个网格被多个节点引用时结果不同，因此以节点区分
+        let params = format!(
+            "gltf {:016x} node {} primitive {}",
+            self.buffers_hash,
+            node.index(),
+            primitive.index()
+        );
+        let buffers = self.buffers;
+        let mesh = load_mesh(self.cache.as_ref(), &params, self.bvh, || {
+            let data = read_primitive(primitive, buffers, world, &inverse.transpose());
+            let face_count = data.indices.len();
+            (data, vec![0; face_count])
+        });
+        let gltf_material = primitive.material();
+        let images = self.images;
+        let material = self
+            .materials
+            .entry(gltf_material.index())
+            .or_insert_with(|| Arc::new(build_material(&gltf_material, images)))
+            .clone();
+        self.world.push(Mesh::with_layout(
+            mesh.data,
+            vec![material],
+            mesh.face_materials,
+            mesh.layout,
+            self.bvh,
+        ));
+    }
+}
+
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    world: &Mat44,
+    normal_matrix: &Mat44,
+) -> MeshData {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<Point3> = reader
+        .read_positions()
+        .expect("glTF primitive has no positions.")
+        .map(|p| world.transform_point(Point3::new(p[0] as f64, p[1] as f64, p[2] as f64)))
+        .collect();
+    let flat: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let indices: Vec<[usize; 3]> = flat
+        .chunks_exact(3)
+        .map(|c| [c[0] as usize, c[1] as usize, c[2] as usize])
+        .collect();
+    let mut data = MeshData::new(positions, indices);
+    if let Some(normals) = reader.read_normals() {
+        data = data.with_normals(
+            normals
+                .map(|n| {
+                    normal_matrix
+                        .transform_vector(Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64))
+                        .normalize()
+                })
+                .collect(),
+        );
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        // glTF 纹理坐标原点在左上角
+        data = data.with_uvs(
+            uvs.into_f32()
+                .map(|uv| Vec2::new(uv[0] as f64, 1.0 - uv[1] as f64))
+                .collect(),
+        );
+    }
+    data
+}
+
+pub fn load_gltf(path: &str, bvh: &BvhOptions, cache: Option<&MeshCache>) -> GltfScene {
+    let (document, buffers, images) = gltf::import(path).expect("Failed to load glTF file.");
+    let chunks: Vec<&[u8]> = buffers.iter().map(|buffer| &buffer[..]).collect();
+    let mut loader = SceneLoader {
+        buffers: &buffers,
+        buffers_hash: cache::fnv1a(&chunks),
+        images: &images,
+        materials: HashMap::new(),
+        bvh,
+        cache: cache.map(|cache| cache.source(path)),
+        world: HittableList::new(),
+        camera: None,
+    };
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            loader.visit(node, &Mat44::identity());
+        }
+    }
+    GltfScene {
+        world: loader.world,
+        camera: loader.camera,
+    }
+}
@@ -0,0 +1,5 @@
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod vox;
@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    material::{Dielectric, DiffuseLight, Lambertian, MaterialEnum, Metal},
+    mesh::MeshData,
+    texture::{ImageTexture, SolidTexture, TextureEnum},
+    vec::{Point3, Vec2, Vec3},
+};
+
+pub struct ObjModel {
+    pub mesh: MeshData,
+    pub materials: Vec<Arc<MaterialEnum>>,
+    // 每个三角形对应 materials 中的下标
+    pub face_materials: Vec<usize>,
+}
+
+#[derive(Default)]
+struct MtlMaterial {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+    map_kd: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    fn build(self) -> MaterialEnum {
+        let kd = self.kd.unwrap_or(Color::from_single(0.8));
+        let ks = self.ks.unwrap_or(Color::zero());
+        let max = |c: Color| c.0.max(c.1).max(c.2);
+        if let Some(ke) = self.ke.filter(|ke| max(*ke) > 0.0) {
+            return MaterialEnum::DiffuseLight(DiffuseLight::new(ke, 1.0));
+        }
+        if matches!(self.illum, Some(4 | 6 | 7)) || self.dissolve.is_some_and(|d| d < 1.0) {
+            return MaterialEnum::Dielectric(Dielectric::new(self.ni.unwrap_or(1.5)));
+        }
+        let texture = match self.map_kd {
+            Some(path) => TextureEnum::ImageTexture(ImageTexture::from_path(&path)),
+            None => TextureEnum::SolidTexture(SolidTexture::new(kd)),
+        };
+        if self.illum == Some(3) || max(ks) > max(kd) {
+            // Phong 指数越大越接近镜面，换算成 Metal 的 fuzz
+            let fuzz = (2.0 / (self.ns.unwrap_or(0.0) + 2.0)).sqrt();
+            let texture = if max(ks) > 0.0 {
+                TextureEnum::SolidTexture(SolidTexture::new(ks))
+            } else {
+                texture
+            };
+            return MaterialEnum::Metal(Metal::new(texture, fuzz));
+        }
+        MaterialEnum::Lambertian(Lambertian::new(texture))
+    }
+}
+
+fn parse_floats<const N: usize>(args: &[&str]) -> [f64; N] {
+    std::array::from_fn(|i| args.get(i).and_then(|s| s.parse().ok()).unwrap_or(0.0))
+}
+
+fn parse_color(args: &[&str]) -> Color {
+    let [r, g, b] = parse_floats::<3>(args);
+    Color::new(r, g, b)
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, MtlMaterial>) {
+    let Ok(contents) = fs::read_to_string(path) else {
+        println!("未找到材质库 '{}'，将使用默认材质。", path.display());
+        return;
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut current: Option<String> = None;
+    for line in contents.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = tokens.split_first() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            let name = args.join(" ");
+            materials.insert(name.clone(), MtlMaterial::default());
+            current = Some(name);
+            continue;
+        }
+        let Some(material) = current.as_ref().and_then(|name| materials.get_mut(name)) else {
+            continue;
+        };
+        match keyword {
+            "Kd" => material.kd = Some(parse_color(args)),
+            "Ks" => material.ks = Some(parse_color(args)),
+            "Ke" => material.ke = Some(parse_color(args)),
+            "Ns" => material.ns = args.first().and_then(|s| s.parse().ok()),
+            "Ni" => material.ni = args.first().and_then(|s| s.parse().ok()),
+            "d" => material.dissolve = args.first().and_then(|s| s.parse().ok()),
+            "Tr" => {
+                material.dissolve = args
+                    .first()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .map(|tr| 1.0 - tr)
+            }
+            "illum" => material.illum = args.first().and_then(|s| s.parse().ok()),
+            // 贴图路径前可能带有 -s/-o 等选项，取最后一个参数
+            "map_Kd" => material.map_kd = args.last().map(|file| dir.join(file)),
+            _ => {}
+        }
+    }
+}
+
+// OBJ 的索引从 1 开始，负数表示从末尾倒数
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    (0..len as i64)
+        .contains(&resolved)
+        .then_some(resolved as usize)
+}
+
+// 按 usemtl 出现的顺序编号的材质，0 为没有指定材质时的默认材质
+struct MaterialTable {
+    libraries: HashMap<String, MtlMaterial>,
+    names: Vec<Option<String>>,
+}
+
+impl MaterialTable {
+    fn new() -> Self {
+        MaterialTable {
+            libraries: HashMap::new(),
+            names: vec![None],
+        }
+    }
+
+    // 处理 mtllib 和 usemtl，返回 usemtl 选中的材质编号
+    fn parse(&mut self, dir: &Path, keyword: &str, args: &[&str]) -> Option<usize> {
+        match keyword {
+            "mtllib" => {
+                for file in args {
+                    load_mtl(&dir.join(file), &mut self.libraries);
+                }
+                None
+            }
+            "usemtl" => {
+                let name = Some(args.join(" "));
+                Some(match self.names.iter().position(|n| *n == name) {
+                    Some(idx) => idx,
+                    None => {
+                        self.names.push(name);
+                        self.names.len() - 1
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn build(mut self) -> Vec<Arc<MaterialEnum>> {
+        self.names
+            .into_iter()
+            .map(|name| {
+                Arc::new(
+                    name.and_then(|name| self.libraries.remove(&name))
+                        .map_or_else(MaterialEnum::default, MtlMaterial::build),
+                )
+            })
+            .collect()
+    }
+}
+
+// 只读取材质，编号与 load_obj 相同，网格从缓存加载时使用
+pub fn load_obj_materials(path: &str) -> Vec<Arc<MaterialEnum>> {
+    let contents = fs::read_to_string(path).expect("Failed to open OBJ file.");
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut table = MaterialTable::new();
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.starts_with("mtllib") || line.starts_with("usemtl") {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            table.parse(dir, tokens[0], &tokens[1..]);
+        }
+    }
+    table.build()
+}
+
+pub fn load_obj(path: &str) -> ObjModel {
+    let contents = fs::read_to_string(path).expect("Failed to open OBJ file.");
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<Vec2> = vec![];
+
+    // 以 (v, vt, vn) 三元组去重得到网格顶点
+    let mut vertex_map: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+    let mut corners: Vec<(usize, Option<usize>, Option<usize>)> = vec![];
+    let mut indices: Vec<[usize; 3]> = vec![];
+    let mut table = MaterialTable::new();
+    let mut face_materials: Vec<usize> = vec![];
+    let mut current_material = 0;
+
+    for line in contents.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = tokens.split_first() else {
+            continue;
+        };
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(args);
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(args);
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parse_floats::<2>(args);
+                uvs.push(Vec2::new(u, v));
+            }
+            "mtllib" | "usemtl" => {
+                if let Some(material) = table.parse(dir, keyword, args) {
+                    current_material = material;
+                }
+            }
+            "f" => {
+                let face: Vec<usize> = args
+                    .iter()
+                    .map(|corner| {
+                        let mut parts = corner.split('/');
+                        let v = parts
+                            .next()
+                            .and_then(|s| resolve_index(s, positions.len()))
+                            .expect("Invalid vertex index in OBJ file.");
+                        let vt = parts.next().and_then(|s| resolve_index(s, uvs.len()));
+                        let vn = parts.next().and_then(|s| resolve_index(s, normals.len()));
+                        *vertex_map.entry((v, vt, vn)).or_insert_with(|| {
+                            corners.push((v, vt, vn));
+                            corners.len() - 1
+                        })
+                    })
+                    .collect();
+                // 多边形按扇形三角化
+                for i in 1..face.len().saturating_sub(1) {
+                    indices.push([face[0], face[i], face[i + 1]]);
+                    face_materials.push(current_material);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut mesh = MeshData::new(corners.iter().map(|c| positions[c.0]).collect(), indices);
+    // 只有所有顶点都带有法线/纹理坐标时才使用对应的属性
+    if !corners.is_empty() && corners.iter().all(|c| c.2.is_some()) {
+        mesh = mesh.with_normals(corners.iter().map(|c| normals[c.2.unwrap()]).collect());
+    }
+    if !corners.is_empty() && corners.iter().all(|c| c.1.is_some()) {
+        mesh = mesh.with_uvs(corners.iter().map(|c| uvs[c.1.unwrap()]).collect());
+    }
+    ObjModel {
+        mesh,
+        materials: table.build(),
+        face_materials,
+    }
+}
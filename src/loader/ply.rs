@@ -0,0 +1,255 @@
+use std::{fs, str::SplitAsciiWhitespace};
+
+use crate::{
+    color::Color,
+    mesh::MeshData,
+    vec::{Point3, Vec2, Vec3},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Self {
+        match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => panic!("Unknown PLY property type '{name}'."),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    // 整数类型的颜色分量需要归一化到 [0, 1]
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U8 => 1.0 / 255.0,
+            Self::U16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, ScalarType),
+    List(String, ScalarType, ScalarType),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct BodyReader<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+    tokens: Option<SplitAsciiWhitespace<'a>>,
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, ty: ScalarType) -> f64 {
+        if let Some(tokens) = &mut self.tokens {
+            return tokens
+                .next()
+                .and_then(|s| s.parse().ok())
+                .expect("Unexpected end of PLY data.");
+        }
+        let size = ty.size();
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .expect("Unexpected end of PLY data.");
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        match ty {
+            ScalarType::I8 => buf[0] as i8 as f64,
+            ScalarType::U8 => buf[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(buf),
+        }
+    }
+}
+
+fn parse_header(header: &str) -> (Format, Vec<Element>) {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in header.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", ..] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", ..] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", ..] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().expect("Invalid PLY element count."),
+                properties: vec![],
+            }),
+            ["property", "list", count_ty, item_ty, name] => {
+                if let Some(element) = elements.last_mut() {
+                    element.properties.push(Property::List(
+                        name.to_string(),
+                        ScalarType::parse(count_ty),
+                        ScalarType::parse(item_ty),
+                    ));
+                }
+            }
+            ["property", ty, name] => {
+                if let Some(element) = elements.last_mut() {
+                    element
+                        .properties
+                        .push(Property::Scalar(name.to_string(), ScalarType::parse(ty)));
+                }
+            }
+            _ => {}
+        }
+    }
+    (format.expect("Missing PLY format line."), elements)
+}
+
+pub fn load_ply(path: &str) -> MeshData {
+    let bytes = fs::read(path).expect("Failed to open PLY file.");
+    let marker = b"end_header";
+    let header_end = bytes
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .expect("Missing PLY end_header.");
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+    let (format, elements) = parse_header(&header);
+    // 跳过 end_header 之后的换行（可能是 \r\n）
+    let mut body_start = header_end + marker.len();
+    while body_start < bytes.len() && bytes[body_start] != b'\n' {
+        body_start += 1;
+    }
+    let body = &bytes[(body_start + 1).min(bytes.len())..];
+    let mut reader = BodyReader {
+        format,
+        bytes: body,
+        pos: 0,
+        tokens: (format == Format::Ascii).then(|| {
+            std::str::from_utf8(body)
+                .expect("Invalid ASCII PLY data.")
+                .split_ascii_whitespace()
+        }),
+    };
+
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<Vec2> = vec![];
+    let mut colors: Vec<Color> = vec![];
+    let mut indices: Vec<[usize; 3]> = vec![];
+    for element in &elements {
+        let has = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .any(|p| matches!(p, Property::Scalar(n, _) if names.contains(&n.as_str())))
+        };
+        let (has_normal, has_uv, has_color) = (
+            has(&["nx"]),
+            has(&["u", "s", "texture_u"]),
+            has(&["red", "diffuse_red"]),
+        );
+        for _ in 0..element.count {
+            let mut v = [0.0; 3];
+            let mut n = [0.0; 3];
+            let mut uv = [0.0; 2];
+            let mut c = [0.0; 3];
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(name, ty) => {
+                        let value = reader.read(*ty);
+                        match name.as_str() {
+                            "x" => v[0] = value,
+                            "y" => v[1] = value,
+                            "z" => v[2] = value,
+                            "nx" => n[0] = value,
+                            "ny" => n[1] = value,
+                            "nz" => n[2] = value,
+                            "u" | "s" | "texture_u" => uv[0] = value,
+                            "v" | "t" | "texture_v" => uv[1] = value,
+                            "red" | "diffuse_red" => c[0] = value * ty.color_scale(),
+                            "green" | "diffuse_green" => c[1] = value * ty.color_scale(),
+                            "blue" | "diffuse_blue" => c[2] = value * ty.color_scale(),
+                            _ => {}
+                        }
+                    }
+                    Property::List(name, count_ty, item_ty) => {
+                        let count = reader.read(*count_ty) as usize;
+                        let items: Vec<usize> =
+                            (0..count).map(|_| reader.read(*item_ty) as usize).collect();
+                        if element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index")
+                        {
+                            for i in 1..count.saturating_sub(1) {
+                                indices.push([items[0], items[i], items[i + 1]]);
+                            }
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                positions.push(Point3::new(v[0], v[1], v[2]));
+                if has_normal {
+                    normals.push(Vec3::new(n[0], n[1], n[2]));
+                }
+                if has_uv {
+                    uvs.push(Vec2::new(uv[0], uv[1]));
+                }
+                if has_color {
+                    colors.push(Color::new(c[0], c[1], c[2]));
+                }
+            }
+        }
+    }
+
+    let mut mesh = MeshData::new(positions, indices);
+    if !normals.is_empty() {
+        mesh = mesh.with_normals(normals);
+    }
+    if !uvs.is_empty() {
+        mesh = mesh.with_uvs(uvs);
+    }
+    if !colors.is_empty() {
+        mesh = mesh.with_colors(colors);
+    }
+    mesh
+}
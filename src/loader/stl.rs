@@ -0,0 +1,135 @@
+use std::{collections::HashMap, fs};
+
+use crate::{
+    mesh::MeshData,
+    vec::{Point3, Vec3},
+};
+
+fn read_f32(bytes: &[u8], offset: usize) -> f64 {
+    f32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ]) as f64
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<[Point3; 3]> {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    (0..count)
+        .map(|i| {
+            // 每个面片 50 字节：法线、三个顶点、2 字节属性
+            let base = 84 + i * 50 + 12;
+            std::array::from_fn(|k| {
+                let offset = base + k * 12;
+                Point3::new(
+                    read_f32(bytes, offset),
+                    read_f32(bytes, offset + 4),
+                    read_f32(bytes, offset + 8),
+                )
+            })
+        })
+        .collect()
+}
+
+fn parse_ascii(contents: &str) -> Vec<[Point3; 3]> {
+    let vertices: Vec<Point3> = contents
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() != Some("vertex") {
+                return None;
+            }
+            let mut coord = || tokens.next().and_then(|s| s.parse().ok()).unwrap_or(0.0);
+            Some(Point3::new(coord(), coord(), coord()))
+        })
+        .collect();
+    vertices
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect()
+}
+
+// 以坐标的位模式焊接重合的顶点
+fn weld_key(p: Point3) -> [u64; 3] {
+    // +0.0 与 -0.0 视为同一个位置
+    [p.0, p.1, p.2].map(|v| (v + 0.0).to_bits())
+}
+
+// smoothing_angle 为角度制，相邻面法线夹角小于该值时共享平滑法线，None 表示平面着色
+pub fn load_stl(path: &str, smoothing_angle: Option<f64>) -> MeshData {
+    let bytes = fs::read(path).expect("Failed to open STL file.");
+    // 二进制 STL 的头部也可能以 "solid" 开头，因此用文件长度判断格式
+    let is_binary = bytes.len() >= 84 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == 84 + count * 50
+    };
+    let facets = if is_binary {
+        parse_binary(&bytes)
+    } else {
+        parse_ascii(&String::from_utf8_lossy(&bytes))
+    };
+
+    let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+    let mut positions: Vec<Point3> = vec![];
+    let faces: Vec<[usize; 3]> = facets
+        .iter()
+        .map(|facet| {
+            facet.map(|p| {
+                *welded.entry(weld_key(p)).or_insert_with(|| {
+                    positions.push(p);
+                    positions.len() - 1
+                })
+            })
+        })
+        .collect();
+
+    let Some(angle) = smoothing_angle else {
+        return MeshData::new(positions, faces);
+    };
+
+    // 未归一化的叉积，长度与面积成正比，用作面积权重
+    let face_normals: Vec<Vec3> = faces
+        .iter()
+        .map(|[a, b, c]| (positions[*b] - positions[*a]).cross(positions[*c] - positions[*a]))
+        .collect();
+    let mut incident: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+    for (face, vertices) in faces.iter().enumerate() {
+        for &v in vertices {
+            incident[v].push(face);
+        }
+    }
+    let cos_threshold = angle.to_radians().cos();
+    let unit = |n: Vec3| {
+        let length = n.length();
+        if length > 0.0 { n / length } else { n }
+    };
+
+    // 同一位置上法线不同的角需要拆分成不同的网格顶点
+    let mut split: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+    let mut out_positions: Vec<Point3> = vec![];
+    let mut out_normals: Vec<Vec3> = vec![];
+    let indices: Vec<[usize; 3]> = faces
+        .iter()
+        .enumerate()
+        .map(|(face, vertices)| {
+            let face_normal = unit(face_normals[face]);
+            vertices.map(|v| {
+                let mut normal = incident[v]
+                    .iter()
+                    .filter(|&&other| unit(face_normals[other]).dot(face_normal) >= cos_threshold)
+                    .fold(Vec3::zero(), |acc, &other| acc + face_normals[other]);
+                if normal.length_squared() == 0.0 {
+                    normal = face_normal;
+                }
+                let normal = unit(normal);
+                *split.entry((v, weld_key(normal))).or_insert_with(|| {
+                    out_positions.push(positions[v]);
+                    out_normals.push(normal);
+                    out_positions.len() - 1
+                })
+            })
+        })
+        .collect();
+    MeshData::new(out_positions, indices).with_normals(out_normals)
+}
@@ -0,0 +1,172 @@
+use std::{fs, sync::Arc};
+
+use crate::{
+    color::Color,
+    material::{Lambertian, MaterialEnum},
+    texture::{SolidTexture, TextureEnum},
+    voxel::VoxelData,
+};
+
+pub struct VoxModel {
+    pub data: VoxelData,
+    // 调色板中的 255 种颜色，体素编号 k 对应 materials[k - 1]
+    pub materials: Vec<Arc<MaterialEnum>>,
+}
+
+// 文件中的长度字段不可信，越界时报错而不是直接 panic 在切片上
+fn slice(bytes: &[u8], start: usize, len: usize) -> &[u8] {
+    start
+        .checked_add(len)
+        .and_then(|end| bytes.get(start..end))
+        .expect("Truncated VOX file.")
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> usize {
+    let chunk = slice(bytes, offset, 4);
+    u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize
+}
+
+// 只读取第一个模型，MagicaVoxel 为 Z 轴向上，转换为 Y 轴向上
+pub fn load_vox(path: &str) -> VoxModel {
+    let bytes = fs::read(path).expect("Failed to open VOX file.");
+    assert!(
+        bytes.len() >= 8 && &bytes[0..4] == b"VOX ",
+        "Not a MagicaVoxel VOX file."
+    );
+
+    let mut size: Option<[usize; 3]> = None;
+    let mut voxels: Option<&[u8]> = None;
+    let mut palette: Option<&[u8]> = None;
+    // 跳过 MAIN 块的头部，其子块依次排列
+    let mut offset = 8 + 12 + read_u32(&bytes, 12);
+    while offset + 12 <= bytes.len() {
+        let id = slice(&bytes, offset, 4);
+        let content_size = read_u32(&bytes, offset + 4);
+        let content = slice(&bytes, offset + 12, content_size);
+        match id {
+            b"SIZE" if size.is_none() => {
+                size = Some([
+                    read_u32(content, 0),
+                    read_u32(content, 4),
+                    read_u32(content, 8),
+                ]);
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = read_u32(content, 0);
+                voxels = Some(slice(content, 4, count * 4));
+            }
+            b"RGBA" => {
+                assert!(content.len() >= 255 * 4, "VOX palette is too short.");
+                palette = Some(content);
+            }
+            _ => {}
+        }
+        // 块内容之后是子块，MAIN 以外的块都没有子块
+        offset += 12 + content_size + read_u32(&bytes, offset + 8);
+    }
+
+    let [sx, sy, sz] = size.expect("VOX file has no SIZE chunk.");
+    let mut data = VoxelData::new([sx, sz, sy]);
+    for voxel in voxels.expect("VOX file has no XYZI chunk.").chunks_exact(4) {
+        let (x, y, z) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize);
+        assert!(
+            x < sx && y < sy && z < sz,
+            "VOX voxel ({x}, {y}, {z}) is outside the model of size ({sx}, {sy}, {sz})."
+        );
+        data.set([x, z, sy - 1 - y], voxel[3] as u16);
+    }
+
+    // 没有 RGBA 块时使用灰色，而不是内置的默认调色板
+    let materials = (0..255)
+        .map(|i| {
+            let color = palette.map_or(Color::from_single(0.8), |palette| {
+                let rgb = &palette[i * 4..i * 4 + 3];
+                Color::new(
+                    rgb[0] as f64 / 255.0,
+                    rgb[1] as f64 / 255.0,
+                    rgb[2] as f64 / 255.0,
+                )
+            });
+            Arc::new(MaterialEnum::Lambertian(Lambertian::new(
+                TextureEnum::SolidTexture(SolidTexture::new(color)),
+            )))
+        })
+        .collect();
+    VoxModel { data, materials }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // 把子块放进 MAIN 块写到临时文件，返回路径
+    fn write_vox(name: &str, children: &[Vec<u8>]) -> String {
+        let children = children.concat();
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend_from_slice(&150u32.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+        let path = env::temp_dir().join(format!("ray_tracing_{}_{name}.vox", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn loads_voxels_with_y_up() {
+        let xyzi = [words(&[1]), vec![1, 2, 3, 7]].concat();
+        let path = write_vox(
+            "valid",
+            &[chunk(b"SIZE", &words(&[2, 3, 4])), chunk(b"XYZI", &xyzi)],
+        );
+        let model = load_vox(&path);
+        assert_eq!(model.data.size, [2, 4, 3]);
+        assert_eq!(model.data.get([1, 3, 0]), 7);
+        assert_eq!(model.data.voxels.iter().filter(|&&v| v != 0).count(), 1);
+        assert_eq!(model.materials.len(), 255);
+    }
+
+    #[test]
+    #[should_panic(expected = "Truncated VOX file.")]
+    fn rejects_truncated_voxel_list() {
+        // XYZI 声明了 5 个体素，实际只有 1 个
+        let xyzi = [words(&[5]), vec![0, 0, 0, 1]].concat();
+        load_vox(&write_vox(
+            "truncated_xyzi",
+            &[chunk(b"SIZE", &words(&[2, 2, 2])), chunk(b"XYZI", &xyzi)],
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Truncated VOX file.")]
+    fn rejects_chunk_past_end_of_file() {
+        let mut size = chunk(b"SIZE", &words(&[2, 2, 2]));
+        size[4..8].copy_from_slice(&1000u32.to_le_bytes());
+        load_vox(&write_vox("truncated_chunk", &[size]));
+    }
+
+    #[test]
+    #[should_panic(expected = "is outside the model")]
+    fn rejects_voxel_outside_model() {
+        let xyzi = [words(&[1]), vec![0, 2, 0, 1]].concat();
+        load_vox(&write_vox(
+            "out_of_range",
+            &[chunk(b"SIZE", &words(&[2, 2, 2])), chunk(b"XYZI", &xyzi)],
+        ));
+    }
+}
@@ -1,43 +1,90 @@
 pub mod aabb;
+pub mod accelerator;
 pub mod bvh;
+pub mod cache;
 pub mod camera;
 pub mod color;
 pub mod config;
+pub mod csg;
+pub mod curve;
 pub mod geometry;
+pub mod grid;
+pub mod heightfield;
 pub mod hittable;
+pub mod kdtree;
+pub mod loader;
 pub mod material;
 pub mod math;
 pub mod matrix;
+pub mod medium;
+pub mod mesh;
 pub mod noise;
+pub mod quadric;
 pub mod random;
 pub mod ray;
+pub mod sdf;
 pub mod texture;
+pub mod transform;
 pub mod vec;
+pub mod voxel;
 
 use std::{sync::Arc, time::Instant};
 
 use crate::{
+    accelerator::{Accelerator, AcceleratorEnum},
+    cache::MeshCache,
     camera::CameraBuilder,
     color::Color,
-    config::{Configurable, build_world, load_config_from_file},
+    config::{
+        AnimationConfig, Configurable, build_accelerator, build_atmosphere, build_bvh_options,
+        build_world, load_config_from_file,
+    },
     geometry::{Quad, Sphere},
     hittable::HittableList,
+    loader::gltf::load_gltf,
     material::Lambertian,
     texture::SolidTexture,
-    vec::{Point3, Vec3},
+    vec::{Point3, Vec2, Vec3},
 };
 
 fn main() {
     let config = load_config_from_file("config.toml");
+    let bvh_options = build_bvh_options(config.bvh.as_ref());
+    let accelerator = build_accelerator(config.accelerator.as_ref(), &bvh_options);
     let mut camera_builder = CameraBuilder::new();
+    let cache = config
+        .cache
+        .as_ref()
+        .map(|config| MeshCache::new(&config.dir));
+    let mut gltf_world = None;
+    if let Some(gltf_config) = &config.gltf {
+        let scene = load_gltf(&gltf_config.path, &bvh_options, cache.as_ref());
+        if let (true, Some(builder)) = (gltf_config.use_camera.unwrap_or(true), scene.camera) {
+            camera_builder = builder;
+        }
+        gltf_world = Some(scene.world);
+    }
     if let Some(camera_config) = &config.camera {
         camera_builder = camera_builder.apply_config(camera_config);
     }
-    let camera = camera_builder.build();
+    if let Some(atmosphere_config) = config.atmosphere {
+        camera_builder = camera_builder.atmosphere(build_atmosphere(atmosphere_config));
+    }
+    let mut camera = camera_builder.build();
     let mut world = HittableList::new();
-    if config.objects.len() > 0 {
-        world = build_world(config.objects);
-    } else {
+    if !config.objects.is_empty() {
+        world = build_world(
+            config.objects,
+            config.prototypes,
+            bvh_options,
+            accelerator,
+            cache,
+        );
+    }
+    if let Some(gltf_world) = gltf_world {
+        world.list.extend(gltf_world.list);
+    }
+    if world.is_empty() {
         world
             .push(Sphere::new(
                 Point3::zero(),
@@ -52,8 +99,26 @@ fn main() {
                 Arc::new(Lambertian::new(SolidTexture::new(Color::from_single(0.6)))),
             ));
     }
+    let mut world = AcceleratorEnum::new(&accelerator, &world.list);
     let start_time = Instant::now();
-    camera.render(&world);
+    match config.animation {
+        None => camera.render(&world, "output.png"),
+        // 帧之间只有物体的位置变化，BVH 只做 refit，网格和 kd 树每帧重新构建
+        Some(AnimationConfig { frames }) => {
+            assert!(frames > 0, "animation.frames 必须大于 0！");
+            for frame in 0..frames {
+                let shutter = Vec2::new(
+                    frame as f64 / frames as f64,
+                    (frame + 1) as f64 / frames as f64,
+                );
+                if world.update(shutter) {
+                    println!("\r第 {frame} 帧重新构建加速结构");
+                }
+                camera.set_shutter(shutter);
+                camera.render(&world, &format!("output_{frame:04}.png"));
+            }
+        }
+    }
     let elapsed_time = start_time.elapsed();
     println!("\r耗时{}秒", elapsed_time.as_secs_f64());
 }
@@ -1,7 +1,9 @@
+use std::f64::consts::PI;
+
 use crate::{
     color::Color,
     hittable::HitRecord,
-    math::{reflect, refract, schlick_approx},
+    math::{orthonormal_basis, reflect, refract, schlick_approx},
     random::{m_random, random_vector_on_sphere},
     ray::Ray,
     texture::{Texture, TextureEnum},
@@ -13,7 +15,7 @@ pub struct ScatterResult {
     pub scattered: Vec3,
 }
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<ScatterResult>;
     fn emit(&self) -> Color {
         Color::zero()
@@ -26,6 +28,7 @@ pub enum MaterialEnum {
     Dielectric(Dielectric),
     DiffuseLight(DiffuseLight),
     Isotropic(Isotropic<TextureEnum>),
+    KajiyaKay(KajiyaKay<TextureEnum>),
 }
 
 impl Default for MaterialEnum {
@@ -42,6 +45,7 @@ impl Material for MaterialEnum {
             Self::Dielectric(m) => m.scatter(ray, record),
             Self::DiffuseLight(m) => m.scatter(ray, record),
             Self::Isotropic(m) => m.scatter(ray, record),
+            Self::KajiyaKay(m) => m.scatter(ray, record),
         }
     }
     fn emit(&self) -> Color {
@@ -51,6 +55,7 @@ impl Material for MaterialEnum {
             Self::Dielectric(m) => m.emit(),
             Self::DiffuseLight(m) => m.emit(),
             Self::Isotropic(m) => m.emit(),
+            Self::KajiyaKay(m) => m.emit(),
         }
     }
 }
@@ -69,7 +74,7 @@ impl<T: Texture> Material for Lambertian<T> {
     fn scatter(&self, _ray: &Ray, record: &HitRecord) -> Option<ScatterResult> {
         let scatter_direction = record.normal + random_vector_on_sphere(record.normal);
         Some(ScatterResult {
-            attenuation: self.texture.value(record.uv, record.p),
+            attenuation: self.texture.value_at(record),
             scattered: scatter_direction.normalize(),
         })
     }
@@ -92,7 +97,7 @@ impl<T: Texture> Material for Metal<T> {
         scatter_direction += Vec3::random_rage(-1.0..1.0) * self.fuzz;
         if scatter_direction.dot(record.normal) > 0.0 {
             Some(ScatterResult {
-                attenuation: self.texture.value(record.uv, record.p),
+                attenuation: self.texture.value_at(record),
                 scattered: scatter_direction.normalize(),
             })
         } else {
@@ -166,8 +171,117 @@ impl<T: Texture> Isotropic<T> {
 impl<T: Texture> Material for Isotropic<T> {
     fn scatter(&self, _ray: &Ray, record: &HitRecord) -> Option<ScatterResult> {
         Some(ScatterResult {
-            attenuation: self.texture.value(record.uv, record.p),
+            attenuation: self.texture.value_at(record),
             scattered: Vec3::random_rage(-1.0..1.0).normalize(),
         })
     }
 }
+
+// 毛发的 Kajiya-Kay 模型，需要命中记录中的切线，没有切线时任取一个与法线垂直的方向
+pub struct KajiyaKay<T: Texture> {
+    texture: T,
+    specular: Color,
+    exponent: f64,
+}
+
+impl<T: Texture> KajiyaKay<T> {
+    pub fn new(texture: T, specular: Color, exponent: f64) -> Self {
+        KajiyaKay {
+            texture,
+            specular,
+            exponent,
+        }
+    }
+}
+
+impl<T: Texture> Material for KajiyaKay<T> {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<ScatterResult> {
+        let tangent = record
+            .tangent
+            .unwrap_or_else(|| orthonormal_basis(record.normal).0);
+        // 漫反射与高光各以一半的概率采样，权重需要乘 2
+        if m_random::<f64>() < 0.5 {
+            // 漫反射正比于 sin(T, L)，细丝没有正反面，在整个球面上采样
+            let scattered = Vec3::random_rage(-1.0..1.0).normalize();
+            let sin = (1.0 - tangent.dot(scattered).powi(2)).max(0.0).sqrt();
+            // sin 在球面上的均值为 π/4
+            return Some(ScatterResult {
+                attenuation: self.texture.value_at(record) * sin * 4.0 / PI * 2.0,
+                scattered,
+            });
+        }
+        // 高光集中在以切线为轴的圆锥上，出射方向沿切线的分量与入射方向相同
+        let direction = ray.direction.normalize();
+        let cos_t = direction.dot(tangent).clamp(-1.0, 1.0);
+        let sin_t = (1.0 - cos_t * cos_t).sqrt();
+        let (b1, b2) = orthonormal_basis(tangent);
+        let phi = 2.0 * PI * m_random::<f64>();
+        let cone = cos_t * tangent + sin_t * (phi.cos() * b1 + phi.sin() * b2);
+        // 按 Phong 瓣在圆锥方向附近扰动
+        let cos_a = m_random::<f64>().powf(1.0 / (self.exponent + 1.0));
+        let sin_a = (1.0 - cos_a * cos_a).sqrt();
+        let (c1, c2) = orthonormal_basis(cone);
+        let phi = 2.0 * PI * m_random::<f64>();
+        Some(ScatterResult {
+            attenuation: self.specular * 2.0,
+            scattered: cos_a * cone + sin_a * (phi.cos() * c1 + phi.sin() * c2),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PhaseFunction {
+    Isotropic,
+    HenyeyGreenstein { g: f64 },
+    // 两个 Henyey-Greenstein 瓣按 weight 混合，weight 为第一个瓣的比例
+    TwoLobe { g1: f64, g2: f64, weight: f64 },
+}
+
+impl PhaseFunction {
+    // direction 为入射光线的传播方向，g > 0 时偏向前向散射
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        match *self {
+            Self::Isotropic => Vec3::random_rage(-1.0..1.0).normalize(),
+            Self::HenyeyGreenstein { g } => Self::sample_henyey_greenstein(direction, g),
+            Self::TwoLobe { g1, g2, weight } => {
+                let g = if m_random::<f64>() < weight { g1 } else { g2 };
+                Self::sample_henyey_greenstein(direction, g)
+            }
+        }
+    }
+
+    fn sample_henyey_greenstein(direction: Vec3, g: f64) -> Vec3 {
+        let xi = m_random::<f64>();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * m_random::<f64>();
+        let w = direction.normalize();
+        let (u, v) = orthonormal_basis(w);
+        (u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + w * cos_theta).normalize()
+    }
+}
+
+pub struct PhaseMaterial<T: Texture> {
+    texture: T,
+    phase: PhaseFunction,
+}
+
+impl<T: Texture> PhaseMaterial<T> {
+    pub fn new(texture: T, phase: PhaseFunction) -> Self {
+        PhaseMaterial { texture, phase }
+    }
+}
+
+impl<T: Texture> Material for PhaseMaterial<T> {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            attenuation: self.texture.value_at(record),
+            scattered: self.phase.sample(ray.direction),
+        })
+    }
+}
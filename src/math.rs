@@ -37,6 +37,171 @@ pub fn get_sphere_uv(p: Point3) -> Vec2 {
     Vec2::new(phi * 0.5 / PI, theta / PI)
 }
 
+// 以 n 为第三轴构造正交基，n 需为单位向量
+pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let sign = 1.0_f64.copysign(n.2);
+    let a = -1.0 / (sign + n.2);
+    let b = n.0 * n.1 * a;
+    (
+        Vec3::new(1.0 + sign * n.0 * n.0 * a, sign * b, -sign * n.0),
+        Vec3::new(b, sign + n.1 * n.1 * a, -n.1),
+    )
+}
+
 pub fn hermite_t(t: f64) -> f64 {
     t * t * (3.0 - 2.0 * t)
 }
+
+// 求 x^3 + a x^2 + b x + c = 0 的实根
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let shift = a / 3.0;
+    let p = b - a * shift;
+    let q = 2.0 * shift * shift * shift - b * shift + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    if discriminant >= 0.0 {
+        let d = discriminant.sqrt();
+        vec![(-q / 2.0 + d).cbrt() + (-q / 2.0 - d).cbrt() - shift]
+    } else {
+        // 三个实根，用三角形式避免复数运算
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let phi = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| m * (phi - 2.0 * PI * k as f64 / 3.0).cos() - shift)
+            .collect()
+    }
+}
+
+// 求 x^4 + a x^3 + b x^2 + c x + d = 0 的实根（Ferrari 方法），结果升序排列
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    let shift = a / 4.0;
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+    let quadratic = |b: f64, c: f64, roots: &mut Vec<f64>| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let s = discriminant.sqrt();
+            roots.push((-b - s) / 2.0);
+            roots.push((-b + s) / 2.0);
+        }
+    };
+    let mut roots = vec![];
+    if q.abs() < 1e-12 {
+        // 双二次方程
+        let mut squares = vec![];
+        quadratic(p, r, &mut squares);
+        for z in squares.into_iter().filter(|&z| z >= 0.0) {
+            roots.push(-z.sqrt());
+            roots.push(z.sqrt());
+        }
+    } else {
+        // 预解三次方程的最大根一定为正
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+        let s = (2.0 * m).sqrt();
+        quadratic(-s, p / 2.0 + m + q / (2.0 * s), &mut roots);
+        quadratic(s, p / 2.0 + m - q / (2.0 * s), &mut roots);
+    }
+    let mut roots: Vec<f64> = roots.into_iter().map(|y| y - shift).collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::m_random_range;
+
+    // 由根展开得到首项系数为 1 的多项式的其余系数
+    fn coefficients(roots: &[f64]) -> Vec<f64> {
+        let mut coefficients = vec![1.0];
+        for &root in roots {
+            let mut next = coefficients.clone();
+            next.push(0.0);
+            for (i, c) in coefficients.iter().enumerate() {
+                next[i + 1] -= root * c;
+            }
+            coefficients = next;
+        }
+        coefficients.remove(0);
+        coefficients
+    }
+
+    fn assert_roots(mut actual: Vec<f64>, mut expected: Vec<f64>, tolerance: f64) {
+        actual.sort_by(f64::total_cmp);
+        expected.sort_by(f64::total_cmp);
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < tolerance, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn cubic_with_three_real_roots() {
+        let c = coefficients(&[1.0, 2.0, 3.0]);
+        assert_roots(solve_cubic(c[0], c[1], c[2]), vec![1.0, 2.0, 3.0], 1e-9);
+    }
+
+    #[test]
+    fn cubic_with_one_real_root() {
+        // (x - 2)(x^2 + 1)
+        assert_roots(solve_cubic(-2.0, 1.0, -2.0), vec![2.0], 1e-9);
+        assert_roots(solve_cubic(0.0, 0.0, -8.0), vec![2.0], 1e-9);
+    }
+
+    #[test]
+    fn quartic_with_four_real_roots() {
+        let c = coefficients(&[-2.0, -1.0, 1.0, 3.0]);
+        assert_roots(
+            solve_quartic(c[0], c[1], c[2], c[3]),
+            vec![-2.0, -1.0, 1.0, 3.0],
+            1e-7,
+        );
+    }
+
+    #[test]
+    fn biquadratic_quartic() {
+        // (x^2 - 1)(x^2 - 4)，q 为 0
+        assert_roots(
+            solve_quartic(0.0, -5.0, 0.0, 4.0),
+            vec![-2.0, -1.0, 1.0, 2.0],
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn quartic_without_real_roots() {
+        // (x^2 + 1)(x^2 + 4)
+        assert!(solve_quartic(0.0, 5.0, 0.0, 4.0).is_empty());
+        // (x^2 + 2x + 2)(x^2 - 4x + 5)
+        let c = [-2.0, -1.0, 2.0, 10.0];
+        assert!(solve_quartic(c[0], c[1], c[2], c[3]).is_empty());
+    }
+
+    #[test]
+    fn quartic_with_two_real_roots() {
+        // (x - 1)(x + 3)(x^2 + 1)
+        let c = [2.0, -2.0, 2.0, -3.0];
+        assert_roots(solve_quartic(c[0], c[1], c[2], c[3]), vec![-3.0, 1.0], 1e-9);
+    }
+
+    #[test]
+    fn random_quartics() {
+        for _ in 0..1000 {
+            let mut roots: Vec<f64> = (0..4).map(|_| m_random_range(-5.0..5.0)).collect();
+            roots.sort_by(f64::total_cmp);
+            // 相距过近的根在数值上不可分辨
+            if roots.windows(2).any(|pair| pair[1] - pair[0] < 0.1) {
+                continue;
+            }
+            let c = coefficients(&roots);
+            let actual = solve_quartic(c[0], c[1], c[2], c[3]);
+            assert_roots(actual, roots, 1e-5);
+        }
+    }
+}
@@ -9,17 +9,215 @@ impl Mat33 {
     pub fn new(data: [[f64; 3]; 3]) -> Self {
         Mat33 { data }
     }
+
+    pub fn identity() -> Self {
+        Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| if row == col { 1.0 } else { 0.0 })
+        }))
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row][col]
+    }
+
+    pub fn transpose(&self) -> Self {
+        Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| self.data[col][row])
+        }))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.data;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // 伴随矩阵除以行列式，矩阵奇异时返回 None
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let m = &self.data;
+        let cofactor = |row: usize, col: usize| {
+            let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+            let (c0, c1) = ((col + 1) % 3, (col + 2) % 3);
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        Some(Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| cofactor(col, row) / determinant)
+        })))
+    }
+
+    pub fn mix(a: Mat33, b: Mat33, t: f64) -> Self {
+        Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| (1.0 - t) * a.data[row][col] + t * b.data[row][col])
+        }))
+    }
+
+    pub fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Mat33::new(self.data.map(|row| row.map(&f)))
+    }
+}
+
+impl std::ops::Mul for Mat33 {
+    type Output = Mat33;
+    fn mul(self, rhs: Mat33) -> Self::Output {
+        Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| (0..3).map(|k| self.data[row][k] * rhs.data[k][col]).sum())
+        }))
+    }
 }
 
 impl std::ops::Mul<Vec3> for Mat33 {
     type Output = Vec3;
     fn mul(self, rhs: Vec3) -> Self::Output {
-        let mut tmp = [0.0; 3];
-        for row in 0..3 {
-            for col in 0..3 {
-                tmp[row] += self.data[row][col] * rhs[col];
+        let tmp: [f64; 3] =
+            std::array::from_fn(|row| (0..3).map(|col| self.data[row][col] * rhs[col]).sum());
+        Vec3::new(tmp[0], tmp[1], tmp[2])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Mat44 {
+    data: [[f64; 4]; 4],
+}
+
+impl Mat44 {
+    pub fn new(data: [[f64; 4]; 4]) -> Self {
+        Mat44 { data }
+    }
+
+    pub fn identity() -> Self {
+        Mat44::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| if row == col { 1.0 } else { 0.0 })
+        }))
+    }
+
+    pub fn from_mat33(m: Mat33) -> Self {
+        let mut data = Mat44::identity().data;
+        for (row, values) in m.data.iter().enumerate() {
+            data[row][..3].copy_from_slice(values);
+        }
+        Mat44::new(data)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row][col]
+    }
+
+    // 左上角的 3x3 线性部分
+    pub fn to_mat33(&self) -> Mat33 {
+        Mat33::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| self.data[row][col])
+        }))
+    }
+
+    pub fn transpose(&self) -> Self {
+        Mat44::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| self.data[col][row])
+        }))
+    }
+
+    // 部分主元的高斯-约旦消元，矩阵奇异时返回 None
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.data;
+        let mut inv = Mat44::identity().data;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
             }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Mat44::new(inv))
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.data;
+        let tmp: [f64; 4] = std::array::from_fn(|row| {
+            m[row][0] * p.0 + m[row][1] * p.1 + m[row][2] * p.2 + m[row][3]
+        });
+        if tmp[3] == 1.0 || tmp[3] == 0.0 {
+            Vec3::new(tmp[0], tmp[1], tmp[2])
+        } else {
+            Vec3::new(tmp[0], tmp[1], tmp[2]) / tmp[3]
         }
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.data;
+        let tmp: [f64; 3] =
+            std::array::from_fn(|row| m[row][0] * v.0 + m[row][1] * v.1 + m[row][2] * v.2);
         Vec3::new(tmp[0], tmp[1], tmp[2])
     }
 }
+
+impl std::ops::Mul for Mat44 {
+    type Output = Mat44;
+    fn mul(self, rhs: Mat44) -> Self::Output {
+        Mat44::new(std::array::from_fn(|row| {
+            std::array::from_fn(|col| (0..4).map(|k| self.data[row][k] * rhs.data[k][col]).sum())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::m_random_range;
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        for _ in 0..100 {
+            let m = Mat44::new(std::array::from_fn(|_| {
+                std::array::from_fn(|_| m_random_range(-5.0..5.0))
+            }));
+            let Some(inverse) = m.inverse() else {
+                continue;
+            };
+            let identity = Mat44::identity();
+            for product in [inverse * m, m * inverse] {
+                for row in 0..4 {
+                    for col in 0..4 {
+                        let error = product.get(row, col) - identity.get(row, col);
+                        assert!(error.abs() < 1e-7, "{product:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        // 第三行是前两行之和
+        let m = Mat44::new([
+            [1.0, 2.0, 3.0, 4.0],
+            [0.0, 1.0, 5.0, 2.0],
+            [1.0, 3.0, 8.0, 6.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(m.inverse().is_none());
+        assert!(
+            Mat33::new([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 0.0]])
+                .inverse()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::fs;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    material::{Material, PhaseFunction, ScatterResult},
+    noise::PerlinNoise,
+    random::m_random,
+    ray::Ray,
+    texture::Texture,
+    vec::{Point3, Vec2, Vec3},
+};
+
+pub trait Density: Send + Sync {
+    fn density(&self, p: Point3) -> f64;
+    // 密度的上界，用作 delta tracking 的 majorant
+    fn max_density(&self) -> f64;
+}
+
+pub enum DensityEnum {
+    Constant(f64),
+    Turbulence(TurbulenceDensity),
+    VoxelGrid(VoxelDensity),
+    Procedural(ProceduralDensity),
+}
+
+impl Density for DensityEnum {
+    fn density(&self, p: Point3) -> f64 {
+        match self {
+            Self::Constant(d) => *d,
+            Self::Turbulence(d) => d.density(p),
+            Self::VoxelGrid(d) => d.density(p),
+            Self::Procedural(d) => d.density(p),
+        }
+    }
+    fn max_density(&self) -> f64 {
+        match self {
+            Self::Constant(d) => *d,
+            Self::Turbulence(d) => d.max_density(),
+            Self::VoxelGrid(d) => d.max_density(),
+            Self::Procedural(d) => d.max_density(),
+        }
+    }
+}
+
+pub struct TurbulenceDensity {
+    noise: PerlinNoise,
+    density: f64,
+    scale: f64,
+    depth: usize,
+}
+
+impl TurbulenceDensity {
+    pub fn new(density: f64, scale: f64, depth: usize) -> Self {
+        TurbulenceDensity {
+            noise: PerlinNoise::new(),
+            density,
+            scale,
+            depth,
+        }
+    }
+}
+
+impl Density for TurbulenceDensity {
+    fn density(&self, p: Point3) -> f64 {
+        self.density * self.noise.turb(p * self.scale, self.depth).abs()
+    }
+    fn max_density(&self) -> f64 {
+        self.density
+    }
+}
+
+pub struct VoxelDensity {
+    resolution: [usize; 3],
+    data: Vec<f64>,
+    min: Point3,
+    size: Vec3,
+    max: f64,
+}
+
+impl VoxelDensity {
+    // `data` 按 x 最快、z 最慢的顺序排列，体素覆盖 `a` 到 `b` 的范围
+    pub fn new(resolution: [usize; 3], data: Vec<f64>, a: Point3, b: Point3) -> Self {
+        let [nx, ny, nz] = resolution;
+        assert_eq!(
+            data.len(),
+            nx * ny * nz,
+            "Voxel data length does not match resolution."
+        );
+        let max = data.iter().cloned().fold(0.0, f64::max);
+        let min = a.min(b);
+        VoxelDensity {
+            resolution,
+            data,
+            min,
+            size: a.max(b) - min,
+            max,
+        }
+    }
+
+    // 读取按 little-endian f32 存储的原始体素文件
+    pub fn from_file(path: &str, resolution: [usize; 3], a: Point3, b: Point3) -> Self {
+        let bytes = fs::read(path).expect("Failed to open voxel density file.");
+        let data = bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+            .collect();
+        VoxelDensity::new(resolution, data, a, b)
+    }
+
+    fn at(&self, i: usize, j: usize, k: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(k * ny + j) * nx + i]
+    }
+}
+
+impl Density for VoxelDensity {
+    fn density(&self, p: Point3) -> f64 {
+        let mut base = [0usize; 3];
+        let mut fract = [0.0; 3];
+        for axis in 0..3 {
+            let local = (p[axis] - self.min[axis]) / self.size[axis];
+            if !(0.0..=1.0).contains(&local) {
+                return 0.0;
+            }
+            // 体素值位于体素中心，在相邻中心之间做三线性插值
+            let n = self.resolution[axis];
+            let x = (local * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            base[axis] = (x.floor() as usize).min(n.saturating_sub(2));
+            fract[axis] = x - base[axis] as f64;
+        }
+        let mut result = 0.0;
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let weight = (if i == 0 { 1.0 - fract[0] } else { fract[0] })
+                        * (if j == 0 { 1.0 - fract[1] } else { fract[1] })
+                        * (if k == 0 { 1.0 - fract[2] } else { fract[2] });
+                    let idx = [
+                        (base[0] + i).min(self.resolution[0] - 1),
+                        (base[1] + j).min(self.resolution[1] - 1),
+                        (base[2] + k).min(self.resolution[2] - 1),
+                    ];
+                    result += weight * self.at(idx[0], idx[1], idx[2]);
+                }
+            }
+        }
+        result
+    }
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+type DensityFn = Box<dyn Fn(Point3) -> f64 + Send + Sync>;
+
+pub struct ProceduralDensity {
+    func: DensityFn,
+    max: f64,
+}
+
+impl ProceduralDensity {
+    // `max` 必须是 `func` 在介质内的上界，否则采样会有偏
+    pub fn new(func: impl Fn(Point3) -> f64 + Send + Sync + 'static, max: f64) -> Self {
+        ProceduralDensity {
+            func: Box::new(func),
+            max,
+        }
+    }
+
+    // 从球心向外按 `(1 - r / radius)^falloff` 衰减的密度
+    pub fn sphere_falloff(center: Point3, radius: f64, density: f64, falloff: f64) -> Self {
+        ProceduralDensity::new(
+            move |p| {
+                let r = (p - center).length() / radius;
+                if r >= 1.0 {
+                    0.0
+                } else {
+                    density * (1.0 - r).powf(falloff)
+                }
+            },
+            density,
+        )
+    }
+}
+
+impl Density for ProceduralDensity {
+    fn density(&self, p: Point3) -> f64 {
+        (self.func)(p).max(0.0)
+    }
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+pub struct VolumeMaterial<T: Texture> {
+    texture: T,
+    phase: PhaseFunction,
+    scatter_probability: f64,
+    emission: Color,
+}
+
+impl<T: Texture> Material for VolumeMaterial<T> {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<ScatterResult> {
+        if m_random::<f64>() < self.scatter_probability {
+            Some(ScatterResult {
+                attenuation: self.texture.value_at(record),
+                scattered: self.phase.sample(ray.direction),
+            })
+        } else {
+            None
+        }
+    }
+    fn emit(&self) -> Color {
+        // 每次真实碰撞都按 sigma_a / sigma_t 的比例贡献自发光
+        self.emission * (1.0 - self.scatter_probability)
+    }
+}
+
+pub struct HeterogeneousMedium<G: Hittable, D: Density, T: Texture> {
+    boundary: Box<G>,
+    density: D,
+    sigma_t: f64,
+    material: VolumeMaterial<T>,
+}
+
+impl<G: Hittable, D: Density, T: Texture> HeterogeneousMedium<G, D, T> {
+    // 实际的吸收/散射系数为 `density(p) * sigma_a` 和 `density(p) * sigma_s`
+    pub fn new(
+        boundary: G,
+        density: D,
+        sigma_a: f64,
+        sigma_s: f64,
+        texture: T,
+        phase: PhaseFunction,
+        emission: Color,
+    ) -> Self {
+        let sigma_t = sigma_a + sigma_s;
+        HeterogeneousMedium {
+            boundary: Box::new(boundary),
+            density,
+            sigma_t,
+            material: VolumeMaterial {
+                texture,
+                phase,
+                scatter_probability: if sigma_t > 0.0 {
+                    sigma_s / sigma_t
+                } else {
+                    0.0
+                },
+                emission,
+            },
+        }
+    }
+
+    fn majorant(&self) -> f64 {
+        self.sigma_t * self.density.max_density()
+    }
+
+    // 光线在边界内与 `t_range` 重叠的区间
+    fn inside_interval(&self, ray: &Ray, t_range: Vec2) -> Option<Vec2> {
+        let rec1 = self
+            .boundary
+            .hit(ray, Vec2::new(f64::NEG_INFINITY, f64::INFINITY))?;
+        let rec2 = self
+            .boundary
+            .hit(ray, Vec2::new(rec1.t + 0.00001, f64::INFINITY))?;
+        let interval = Vec2::new(rec1.t.max(t_range.0).max(0.0), rec2.t.min(t_range.1));
+        if interval.0 < interval.1 {
+            Some(interval)
+        } else {
+            None
+        }
+    }
+
+    // 在 majorant 下采样下一个（可能是虚拟的）碰撞点的参数
+    fn next_collision(&self, ray: &Ray, t: f64) -> f64 {
+        let step = -(1.0 - m_random::<f64>()).ln() / self.majorant();
+        t + step / ray.direction.length()
+    }
+
+    // 用 ratio tracking 无偏估计 `t_range` 内的透射率
+    pub fn transmittance(&self, ray: &Ray, t_range: Vec2) -> f64 {
+        let majorant = self.majorant();
+        let Some(interval) = self.inside_interval(ray, t_range) else {
+            return 1.0;
+        };
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+        let mut transmittance = 1.0;
+        let mut t = interval.0;
+        loop {
+            t = self.next_collision(ray, t);
+            if t >= interval.1 {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.sigma_t * self.density.density(ray.at(t)) / majorant;
+        }
+    }
+}
+
+impl<G: Hittable + 'static, D: Density, T: Texture + 'static> Hittable
+    for HeterogeneousMedium<G, D, T>
+{
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let interval = self.inside_interval(ray, t_range)?;
+        // delta tracking：按 majorant 采样，以 sigma_t(p) / majorant 的概率接受真实碰撞
+        let mut t = interval.0;
+        loop {
+            t = self.next_collision(ray, t);
+            if t >= interval.1 {
+                return None;
+            }
+            let p = ray.at(t);
+            if m_random::<f64>() * majorant < self.sigma_t * self.density.density(p) {
+                return Some(HitRecord {
+                    p,
+                    normal: Vec3::zero(),
+                    t,
+                    material: &self.material,
+                    front_face: true,
+                    uv: Vec2::zero(),
+                    tangent: None,
+                    vertex_color: None,
+                });
+            }
+        }
+    }
+    // 以 1 - 透射率的概率视为被遮挡
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        m_random::<f64>() >= self.transmittance(ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        self.boundary.bounding_box()
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.boundary.bounding_box_during(shutter)
+    }
+}
+
+// 作用于整个场景的指数高度雾，不需要边界几何体
+#[derive(Debug)]
+pub struct Atmosphere {
+    density: f64,
+    albedo: Color,
+    height_falloff: f64,
+    base_height: f64,
+    phase: PhaseFunction,
+}
+
+impl Atmosphere {
+    // 高度 y 处的密度为 `density * exp(-height_falloff * (y - base_height))`，
+    // height_falloff 为 0 时即为均匀介质
+    pub fn new(
+        density: f64,
+        albedo: Color,
+        height_falloff: f64,
+        base_height: f64,
+        phase: PhaseFunction,
+    ) -> Self {
+        Atmosphere {
+            density,
+            albedo,
+            height_falloff,
+            base_height,
+            phase,
+        }
+    }
+
+    // 在 t_range 对应的线段上采样散射点，没有散射时返回 None
+    pub fn sample_scattering(&self, ray: &Ray, t_range: Vec2) -> Option<f64> {
+        // 指数高度雾的光学厚度有解析式，可以直接求逆；
+        // 用 delta tracking 的话，朝下的长光线 majorant 会随深度指数增长
+        let density_origin =
+            self.density * (-self.height_falloff * (ray.origin.1 - self.base_height)).exp();
+        if density_origin <= 0.0 || !density_origin.is_finite() {
+            return None;
+        }
+        let ray_length = ray.direction.length();
+        let tau = -(1.0 - m_random::<f64>()).ln() / (density_origin * ray_length);
+        let k = self.height_falloff * ray.direction.1;
+        let t = if k.abs() < 1e-9 {
+            t_range.0 + tau
+        } else {
+            let remaining = (-k * t_range.0).exp() - tau * k;
+            if remaining <= 0.0 {
+                return None;
+            }
+            -remaining.ln() / k
+        };
+        (t < t_range.1).then_some(t)
+    }
+
+    pub fn scatter(&self, ray: &Ray) -> ScatterResult {
+        ScatterResult {
+            attenuation: self.albedo,
+            scattered: self.phase.sample(ray.direction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::{geometry::Sphere, material::Lambertian, texture::SolidTexture};
+
+    // 单位球内上半部分密度为 1、下半部分为 0.5，穿过球心的光线光学厚度为 1.5
+    #[test]
+    fn ratio_tracking_matches_analytic_transmittance() {
+        let boundary = Sphere::new(
+            Point3::zero(),
+            Point3::zero(),
+            1.0,
+            Arc::new(Lambertian::new(SolidTexture::new(Color::zero()))),
+        );
+        let density = ProceduralDensity::new(|p| if p.1 > 0.0 { 1.0 } else { 0.5 }, 1.0);
+        let medium = HeterogeneousMedium::new(
+            boundary,
+            density,
+            0.5,
+            0.5,
+            SolidTexture::new(Color::one()),
+            PhaseFunction::Isotropic,
+            Color::zero(),
+        );
+        let ray = Ray::new(Point3::new(0.0, -5.0, 0.0), Vec3::from_axis_y(2.0), 0.0);
+        let t_range = Vec2::new(0.0, f64::INFINITY);
+        let count = 100000;
+        let transmittance: f64 = (0..count)
+            .map(|_| medium.transmittance(&ray, t_range))
+            .sum::<f64>()
+            / count as f64;
+        let occluded = (0..count)
+            .filter(|_| medium.occluded(&ray, t_range))
+            .count() as f64
+            / count as f64;
+        let expected = (-1.5f64).exp();
+        assert!(
+            (transmittance - expected).abs() < 0.01,
+            "{transmittance} != {expected}"
+        );
+        assert!(
+            (1.0 - occluded - expected).abs() < 0.01,
+            "{occluded} != {}",
+            1.0 - expected
+        );
+        // 光线没有进入边界时透射率为 1
+        let miss = Ray::new(Point3::new(2.0, -5.0, 0.0), Vec3::from_axis_y(1.0), 0.0);
+        assert_eq!(medium.transmittance(&miss, t_range), 1.0);
+    }
+}
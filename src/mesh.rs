@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    bvh::{BvhLayout, BvhNode, BvhOptions},
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// Möller–Trumbore 求交，返回 (t, u, v)，u、v 为 v1、v2 的重心坐标
+pub fn moller_trumbore(
+    ray: &Ray,
+    v0: Point3,
+    e1: Vec3,
+    e2: Vec3,
+    t_range: Vec2,
+) -> Option<(f64, f64, f64)> {
+    let pvec = ray.direction.cross(e2);
+    let det = e1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin - v0;
+    let u = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let qvec = tvec.cross(e1);
+    let v = ray.direction.dot(qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(qvec) * inv_det;
+    if t < t_range.0 || t > t_range.1 {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+// 共享的顶点/索引缓冲，normals、uvs、colors 为空时表示没有对应的顶点属性
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub colors: Vec<Color>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    pub fn new(positions: Vec<Point3>, indices: Vec<[usize; 3]>) -> Self {
+        if let Some(face) = indices
+            .iter()
+            .position(|face| face.iter().any(|&i| i >= positions.len()))
+        {
+            panic!(
+                "Mesh face {face} references vertex {:?}, but the mesh has only {} vertices.",
+                indices[face],
+                positions.len()
+            );
+        }
+        MeshData {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            colors: vec![],
+            indices,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(
+            normals.len(),
+            self.positions.len(),
+            "Mesh normal count mismatch."
+        );
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<Vec2>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len(), "Mesh uv count mismatch.");
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        assert_eq!(
+            colors.len(),
+            self.positions.len(),
+            "Mesh color count mismatch."
+        );
+        self.colors = colors;
+        self
+    }
+
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        self.indices[face].map(|i| self.positions[i])
+    }
+
+    // 每个三角形的包围盒，用于构建 BVH
+    pub fn face_boxes(&self) -> Vec<AABB> {
+        (0..self.indices.len())
+            .map(|face| {
+                let [a, b, c] = self.vertices(face);
+                AABB::from_aabb(&AABB::new(a, b), &AABB::new(a, c))
+            })
+            .collect()
+    }
+}
+
+struct MeshTriangle<M: Material> {
+    mesh: Arc<MeshData>,
+    face: usize,
+    material: Arc<M>,
+    bbox: AABB,
+}
+
+impl<M: Material + 'static> Hittable for MeshTriangle<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let mesh = self.mesh.as_ref();
+        let [a, b, c] = mesh.vertices(self.face);
+        let (e1, e2) = (b - a, c - a);
+        let (t, u, v) = moller_trumbore(ray, a, e1, e2, t_range)?;
+        let w = 1.0 - u - v;
+        let [i0, i1, i2] = mesh.indices[self.face];
+
+        let geometric_normal = e1.cross(e2).normalize();
+        let front_face = ray.direction.dot(geometric_normal) < 0.0;
+        let normal = if mesh.normals.is_empty() {
+            geometric_normal
+        } else {
+            // 平滑着色法线，翻到与几何法线同一侧
+            let n =
+                (w * mesh.normals[i0] + u * mesh.normals[i1] + v * mesh.normals[i2]).normalize();
+            if n.dot(geometric_normal) < 0.0 { -n } else { n }
+        };
+        let uv = if mesh.uvs.is_empty() {
+            Vec2::new(u, v)
+        } else {
+            w * mesh.uvs[i0] + u * mesh.uvs[i1] + v * mesh.uvs[i2]
+        };
+        Some(HitRecord {
+            p: ray.at(t),
+            normal: if front_face { normal } else { -normal },
+            t,
+            material: self.material.as_ref(),
+            front_face,
+            uv,
+            tangent: None,
+            vertex_color: (!mesh.colors.is_empty())
+                .then(|| w * mesh.colors[i0] + u * mesh.colors[i1] + v * mesh.colors[i2]),
+        })
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+pub struct Mesh {
+    bvh: BvhNode,
+}
+
+impl Mesh {
+    pub fn new<M: Material + 'static>(
+        data: MeshData,
+        material: Arc<M>,
+        options: &BvhOptions,
+    ) -> Self {
+        let face_count = data.indices.len();
+        Mesh::with_materials(data, vec![material], vec![0; face_count], options)
+    }
+
+    // face_materials 为每个三角形在 materials 中的下标
+    pub fn with_materials<M: Material + 'static>(
+        data: MeshData,
+        materials: Vec<Arc<M>>,
+        face_materials: Vec<usize>,
+        options: &BvhOptions,
+    ) -> Self {
+        assert!(!data.indices.is_empty(), "Mesh has no triangles.");
+        let layout = BvhLayout::build(&data.face_boxes(), options);
+        Mesh::with_layout(data, materials, face_materials, layout, options)
+    }
+
+    // 使用已经构建好的 BVH 结构，例如从缓存中读取的
+    pub fn with_layout<M: Material + 'static>(
+        data: MeshData,
+        materials: Vec<Arc<M>>,
+        face_materials: Vec<usize>,
+        layout: BvhLayout,
+        options: &BvhOptions,
+    ) -> Self {
+        assert_eq!(
+            face_materials.len(),
+            data.indices.len(),
+            "Mesh face material count mismatch."
+        );
+        let data = Arc::new(data);
+        let triangles: Vec<Arc<dyn Hittable>> = data
+            .face_boxes()
+            .into_iter()
+            .enumerate()
+            .map(|(face, bbox)| {
+                Arc::new(MeshTriangle {
+                    mesh: data.clone(),
+                    face,
+                    material: materials[face_materials[face]].clone(),
+                    bbox,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+        Mesh {
+            bvh: BvhNode::from_layout(layout, &triangles, options),
+        }
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        self.bvh.hit(ray, t_range)
+    }
+    fn occluded(&self, ray: &Ray, t_range: Vec2) -> bool {
+        self.bvh.occluded(ray, t_range)
+    }
+    fn bounding_box(&self) -> &AABB {
+        self.bvh.bounding_box()
+    }
+    fn bounding_box_during(&self, shutter: Vec2) -> AABB {
+        self.bvh.bounding_box_during(shutter)
+    }
+}
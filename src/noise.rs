@@ -5,19 +5,25 @@ use crate::{
 };
 
 pub struct PerlinNoise {
-    rand_value: [Vec3; 256],
-    perms: ([usize; 256], [usize; 256], [usize; 256]),
+    rand_value: Box<[Vec3; 256]>,
+    perms: Box<([usize; 256], [usize; 256], [usize; 256])>,
+}
+
+impl Default for PerlinNoise {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PerlinNoise {
     pub fn new() -> Self {
         PerlinNoise {
-            rand_value: std::array::from_fn(|_| Vec3::random_rage(-1.0..1.0)),
-            perms: (
+            rand_value: Box::new(std::array::from_fn(|_| Vec3::random_rage(-1.0..1.0))),
+            perms: Box::new((
                 Self::permute(std::array::from_fn(|i| i)),
                 Self::permute(std::array::from_fn(|i| i)),
                 Self::permute(std::array::from_fn(|i| i)),
-            ),
+            )),
         }
     }
 
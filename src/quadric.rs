@@ -0,0 +1,291 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    geometry::Ellipse,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    math::solve_quartic,
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// 绕 Y 轴的方位角，映射到 [0, 1)
+fn azimuth(p: Vec3) -> f64 {
+    (p.2.atan2(p.0) / (2.0 * PI)).rem_euclid(1.0)
+}
+
+fn record<'a>(
+    ray: &Ray,
+    t: f64,
+    outward_normal: Vec3,
+    uv: Vec2,
+    material: &'a dyn Material,
+) -> HitRecord<'a> {
+    let front_face = ray.direction.dot(outward_normal) < 0.0;
+    HitRecord {
+        p: ray.at(t),
+        normal: if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        },
+        t,
+        material,
+        front_face,
+        uv,
+        tangent: None,
+        vertex_color: None,
+    }
+}
+
+// 以 center 为底面中心、沿 +Y 方向的圆柱
+pub struct Cylinder<M: Material> {
+    center: Point3,
+    radius: f64,
+    height: f64,
+    material: Arc<M>,
+    caps: Option<Box<[Ellipse<M>; 2]>>,
+    bbox: AABB,
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(center: Point3, radius: f64, height: f64, capped: bool, material: Arc<M>) -> Self {
+        let top = center + Vec3::from_axis_y(height);
+        Cylinder {
+            center,
+            radius,
+            height,
+            caps: capped.then(|| {
+                Box::new([
+                    Ellipse::disk(
+                        center,
+                        Vec3::from_axis_y(-1.0),
+                        radius,
+                        0.0,
+                        material.clone(),
+                    ),
+                    Ellipse::disk(top, Vec3::from_axis_y(1.0), radius, 0.0, material.clone()),
+                ])
+            }),
+            material,
+            bbox: AABB::new(
+                center - Vec3::new(radius, 0.0, radius),
+                top + Vec3::new(radius, 0.0, radius),
+            ),
+        }
+    }
+}
+
+impl<M: Material + 'static> Hittable for Cylinder<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let a = d.0 * d.0 + d.2 * d.2;
+        let mut result = None;
+        let mut closest_so_far = t_range.1;
+        if a > 1e-12 {
+            let h = o.0 * d.0 + o.2 * d.2;
+            let c = o.0 * o.0 + o.2 * o.2 - self.radius * self.radius;
+            let k = h * h - a * c;
+            if k >= 0.0 {
+                let k = k.sqrt();
+                for t in [(-h - k) / a, (-h + k) / a] {
+                    let y = o.1 + t * d.1;
+                    if t < t_range.0 || t > closest_so_far || y < 0.0 || y > self.height {
+                        continue;
+                    }
+                    let p = o + t * d;
+                    let normal = Vec3::new(p.0, 0.0, p.2) / self.radius;
+                    let uv = Vec2::new(azimuth(p), y / self.height);
+                    result = Some(record(ray, t, normal, uv, self.material.as_ref()));
+                    closest_so_far = t;
+                    break;
+                }
+            }
+        }
+        for cap in self.caps.iter().flat_map(|caps| caps.iter()) {
+            if let Some(rec) = cap.hit(ray, Vec2::new(t_range.0, closest_so_far)) {
+                closest_so_far = rec.t;
+                result = Some(rec);
+            }
+        }
+        result
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+// 底面圆心为 center、顶点在 center + (0, height, 0) 的圆锥
+pub struct Cone<M: Material> {
+    center: Point3,
+    radius: f64,
+    height: f64,
+    material: Arc<M>,
+    cap: Option<Box<Ellipse<M>>>,
+    bbox: AABB,
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(center: Point3, radius: f64, height: f64, capped: bool, material: Arc<M>) -> Self {
+        Cone {
+            center,
+            radius,
+            height,
+            cap: capped.then(|| {
+                Box::new(Ellipse::disk(
+                    center,
+                    Vec3::from_axis_y(-1.0),
+                    radius,
+                    0.0,
+                    material.clone(),
+                ))
+            }),
+            material,
+            bbox: AABB::new(
+                center - Vec3::new(radius, 0.0, radius),
+                center + Vec3::new(radius, height, radius),
+            ),
+        }
+    }
+}
+
+impl<M: Material + 'static> Hittable for Cone<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        // x^2 + z^2 = k (height - y)^2
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let k = (self.radius / self.height).powi(2);
+        let w = self.height - o.1;
+        let a = d.0 * d.0 + d.2 * d.2 - k * d.1 * d.1;
+        let h = o.0 * d.0 + o.2 * d.2 + k * w * d.1;
+        let c = o.0 * o.0 + o.2 * o.2 - k * w * w;
+        let solves = if a.abs() < 1e-12 {
+            // 光线与母线平行，只有一个交点
+            if h.abs() < 1e-12 {
+                vec![]
+            } else {
+                vec![-c / (2.0 * h)]
+            }
+        } else {
+            let discriminant = h * h - a * c;
+            if discriminant < 0.0 {
+                vec![]
+            } else {
+                let s = discriminant.sqrt();
+                let (t0, t1) = ((-h - s) / a, (-h + s) / a);
+                vec![t0.min(t1), t0.max(t1)]
+            }
+        };
+        let mut result = None;
+        let mut closest_so_far = t_range.1;
+        for t in solves {
+            let y = o.1 + t * d.1;
+            if t < t_range.0 || t > closest_so_far || y < 0.0 || y > self.height {
+                continue;
+            }
+            let p = o + t * d;
+            let normal = Vec3::new(p.0, k * (self.height - y), p.2).normalize();
+            let uv = Vec2::new(azimuth(p), y / self.height);
+            result = Some(record(ray, t, normal, uv, self.material.as_ref()));
+            closest_so_far = t;
+            break;
+        }
+        if let Some(rec) = self
+            .cap
+            .as_ref()
+            .and_then(|cap| cap.hit(ray, Vec2::new(t_range.0, closest_so_far)))
+        {
+            result = Some(rec);
+        }
+        result
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+// 以 center 为中心、对称轴为 Y 轴的圆环
+pub struct Torus<M: Material> {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<M>,
+    bbox: AABB,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64, material: Arc<M>) -> Self {
+        let extent = Vec3::new(
+            major_radius + minor_radius,
+            minor_radius,
+            major_radius + minor_radius,
+        );
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+            bbox: AABB::new(center - extent, center + extent),
+        }
+    }
+}
+
+impl<M: Material + 'static> Hittable for Torus<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        if !self.bbox.hit(ray, t_range) {
+            return None;
+        }
+        // 方向归一化，并把起点移到圆环附近以减小四次方程的数值误差
+        let length = ray.direction.length();
+        let d = ray.direction / length;
+        let mut o = ray.origin - self.center;
+        let shift = (-o.dot(d) - (self.major_radius + self.minor_radius)).max(0.0);
+        o += shift * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2)
+        let r2 = self.major_radius * self.major_radius;
+        let g = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        let h = o.dot(d);
+        let coefficients = (
+            4.0 * h,
+            4.0 * h * h + 2.0 * g - 4.0 * r2 * (d.0 * d.0 + d.2 * d.2),
+            4.0 * h * g - 8.0 * r2 * (o.0 * d.0 + o.2 * d.2),
+            g * g - 4.0 * r2 * (o.0 * o.0 + o.2 * o.2),
+        );
+        let (a, b, c, e) = coefficients;
+        // 牛顿迭代修正求根误差
+        let polish = |mut x: f64| {
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c) * x + e;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+                if df.abs() > 1e-12 {
+                    x -= f / df;
+                }
+            }
+            x
+        };
+        let t = solve_quartic(a, b, c, e)
+            .into_iter()
+            .map(|x| (polish(x) + shift) / length)
+            .find(|t| *t >= t_range.0 && *t <= t_range.1)?;
+
+        let p = ray.at(t) - self.center;
+        let ring = Vec3::new(p.0, 0.0, p.2).normalize() * self.major_radius;
+        let normal = (p - ring) / self.minor_radius;
+        let tube =
+            p.1.atan2(Vec3::new(p.0, 0.0, p.2).length() - self.major_radius);
+        let uv = Vec2::new(azimuth(p), (tube / (2.0 * PI)).rem_euclid(1.0));
+        Some(record(
+            ray,
+            t,
+            normal.normalize(),
+            uv,
+            self.material.as_ref(),
+        ))
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
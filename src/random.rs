@@ -26,7 +26,7 @@ pub fn m_random_range<T: SampleUniform, R: SampleRange<T>>(range: R) -> T {
 
 pub fn random_vector_on_sphere(normal: Vec3) -> Vec3 {
     let p = Vec3::random_rage(-1.0..1.0).normalize();
-    return if p.dot(normal) > 0.0 { p } else { -p };
+    if p.dot(normal) > 0.0 { p } else { -p }
 }
 
 pub fn random_in_disk() -> Vec2 {
@@ -34,3 +34,11 @@ pub fn random_in_disk() -> Vec2 {
     let angle = m_random::<f64>() * PI * 2.0;
     Vec2::new(angle.cos() * radius, angle.sin() * radius)
 }
+
+// 在内半径为 inner、外半径为 1 的圆环内均匀采样
+pub fn random_in_unit_ring(inner: f64) -> Vec2 {
+    let inner2 = inner * inner;
+    let radius = (inner2 + m_random::<f64>() * (1.0 - inner2)).sqrt();
+    let angle = m_random::<f64>() * 2.0 * PI;
+    Vec2::new(angle.cos() * radius, angle.sin() * radius)
+}
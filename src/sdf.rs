@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    math::{get_sphere_uv, mix},
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// 距离场表达式树，负值表示在物体内部
+pub enum SdfNode {
+    Sphere {
+        center: Point3,
+        radius: f64,
+    },
+    Box {
+        center: Point3,
+        half_extent: Vec3,
+    },
+    RoundBox {
+        center: Point3,
+        half_extent: Vec3,
+        radius: f64,
+    },
+    // 对称轴为 Y 轴
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: Point3,
+        b: Point3,
+        radius: f64,
+    },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    // k 为过渡区域的宽度
+    SmoothUnion(Box<SdfNode>, Box<SdfNode>, f64),
+    // 从第一个节点中减去第二个节点，k 为 0 时为硬边
+    Subtraction(Box<SdfNode>, Box<SdfNode>, f64),
+    // 按 period 无限重复，分量为 0 的轴不重复
+    Repeat(Box<SdfNode>, Vec3),
+    // 绕 Y 轴扭转，rate 为每单位高度旋转的弧度
+    Twist(Box<SdfNode>, f64),
+}
+
+fn box_distance(p: Vec3, half_extent: Vec3) -> f64 {
+    let q = p.map(f64::abs) - half_extent;
+    q.max(Vec3::zero()).length() + q.0.max(q.1).max(q.2).min(0.0)
+}
+
+impl SdfNode {
+    pub fn distance(&self, p: Point3) -> f64 {
+        match self {
+            Self::Sphere { center, radius } => (p - *center).length() - radius,
+            Self::Box {
+                center,
+                half_extent,
+            } => box_distance(p - *center, *half_extent),
+            Self::RoundBox {
+                center,
+                half_extent,
+                radius,
+            } => box_distance(p - *center, *half_extent - *radius) - radius,
+            Self::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let p = p - *center;
+                let ring = (p.0 * p.0 + p.2 * p.2).sqrt() - major_radius;
+                (ring * ring + p.1 * p.1).sqrt() - minor_radius
+            }
+            Self::Capsule { a, b, radius } => {
+                let (pa, ba) = (p - *a, *b - *a);
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+                (pa - ba * h).length() - radius
+            }
+            Self::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Self::SmoothUnion(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                mix(d2, d1, h) - k * h * (1.0 - h)
+            }
+            Self::Subtraction(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                if *k <= 0.0 {
+                    return d1.max(-d2);
+                }
+                let h = (0.5 - 0.5 * (d1 + d2) / k).clamp(0.0, 1.0);
+                mix(d1, -d2, h) + k * h * (1.0 - h)
+            }
+            Self::Repeat(node, period) => {
+                let wrap = |v: f64, period: f64| {
+                    if period > 0.0 {
+                        v - period * (v / period).round()
+                    } else {
+                        v
+                    }
+                };
+                node.distance(Point3::new(
+                    wrap(p.0, period.0),
+                    wrap(p.1, period.1),
+                    wrap(p.2, period.2),
+                ))
+            }
+            Self::Twist(node, rate) => {
+                let (sin, cos) = (rate * p.1).sin_cos();
+                node.distance(Point3::new(
+                    cos * p.0 - sin * p.2,
+                    p.1,
+                    sin * p.0 + cos * p.2,
+                ))
+            }
+        }
+    }
+
+    // 中心差分求梯度，即未归一化的外法线
+    fn gradient(&self, p: Point3) -> Vec3 {
+        let h = 1e-5;
+        let axis = |offset: Vec3| self.distance(p + offset) - self.distance(p - offset);
+        Vec3::new(
+            axis(Vec3::from_axis_x(h)),
+            axis(Vec3::from_axis_y(h)),
+            axis(Vec3::from_axis_z(h)),
+        )
+    }
+}
+
+// 用球体追踪（sphere tracing）求交的距离场物体，bounds 需要完整包住物体
+pub struct Sdf<M: Material> {
+    root: SdfNode,
+    material: Arc<M>,
+    bbox: AABB,
+    max_steps: usize,
+    // 扭转、平滑运算等会让距离被高估，需要缩小步长
+    step_scale: f64,
+}
+
+impl<M: Material> Sdf<M> {
+    const EPSILON: f64 = 1e-4;
+
+    pub fn new(
+        root: SdfNode,
+        bounds: AABB,
+        max_steps: usize,
+        step_scale: f64,
+        material: Arc<M>,
+    ) -> Self {
+        Sdf {
+            root,
+            material,
+            bbox: bounds,
+            max_steps,
+            step_scale,
+        }
+    }
+}
+
+impl<M: Material + 'static> Hittable for Sdf<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let range = self.bbox.clip(ray, t_range)?;
+        let length = ray.direction.length();
+        // 起点在物体内部时沿负距离前进，以便折射光线能找到出射点
+        let sign = self.root.distance(ray.at(range.0)).signum();
+        let mut t = range.0;
+        for _ in 0..self.max_steps {
+            let distance = sign * self.root.distance(ray.at(t));
+            if distance < Self::EPSILON && t > range.0 {
+                let p = ray.at(t);
+                let normal = self.root.gradient(p).normalize();
+                let front_face = ray.direction.dot(normal) < 0.0;
+                return Some(HitRecord {
+                    p,
+                    normal: if front_face { normal } else { -normal },
+                    t,
+                    material: self.material.as_ref(),
+                    front_face,
+                    uv: get_sphere_uv(normal),
+                    tangent: None,
+                    vertex_color: None,
+                });
+            }
+            t += distance.max(Self::EPSILON) * self.step_scale / length;
+            if t > range.1 {
+                return None;
+            }
+        }
+        None
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Sphere, material::Lambertian, texture::SolidTexture};
+
+    fn material() -> Arc<Lambertian<SolidTexture>> {
+        Arc::new(Lambertian::new(SolidTexture::new(Vec3::from_single(0.5))))
+    }
+
+    fn random_in_ball(radius: f64) -> Vec3 {
+        loop {
+            let p = Vec3::random_rage(-1.0..1.0);
+            if p.length_squared() <= 1.0 {
+                return p * radius;
+            }
+        }
+    }
+
+    #[test]
+    fn sdf_sphere_matches_analytic_sphere() {
+        let (center, radius) = (Point3::new(0.5, -0.3, 1.0), 1.2);
+        let sphere = Sphere::new(center, center, radius, material());
+        let sdf = Sdf::new(
+            SdfNode::Sphere { center, radius },
+            AABB::new(center - radius, center + radius),
+            256,
+            1.0,
+            material(),
+        );
+        let t_range = Vec2::new(0.001, f64::INFINITY);
+        for i in 0..2000 {
+            // 外部光线瞄准球内的点，内部光线从球心附近出发
+            let (origin, target) = if i % 2 == 0 {
+                let origin = center + random_in_ball(1.0).normalize() * 5.0;
+                (origin, center + random_in_ball(radius * 0.9))
+            } else {
+                (
+                    center + random_in_ball(radius * 0.5),
+                    center + random_in_ball(5.0),
+                )
+            };
+            let ray = Ray::new(origin, target - origin, 0.0);
+            let expected = sphere.hit(&ray, t_range).unwrap();
+            let actual = sdf.hit(&ray, t_range).unwrap();
+            let length = ray.direction.length();
+            assert!(
+                (actual.t - expected.t).abs() * length < 1e-3,
+                "{} != {}",
+                actual.t,
+                expected.t
+            );
+            assert!(actual.normal.dot(expected.normal) > 0.999);
+            assert_eq!(actual.front_face, expected.front_face);
+        }
+        // 与球面相距 0.2 以上的光线两者都不相交
+        let ray = Ray::new(
+            center + Vec3::new(-5.0, radius + 0.2, 0.0),
+            Vec3::from_axis_x(1.0),
+            0.0,
+        );
+        assert!(sphere.hit(&ray, t_range).is_none() && sdf.hit(&ray, t_range).is_none());
+    }
+
+    #[test]
+    fn smooth_union_fills_the_gap() {
+        let sphere = |x: f64| {
+            Box::new(SdfNode::Sphere {
+                center: Point3::from_axis_x(x),
+                radius: 1.0,
+            })
+        };
+        let k = 0.5;
+        let node = SdfNode::SmoothUnion(sphere(-1.0), sphere(1.0), k);
+        // 平滑并集不大于普通并集
+        for _ in 0..1000 {
+            let p = random_in_ball(3.0);
+            let hard = sphere(-1.0).distance(p).min(sphere(1.0).distance(p));
+            assert!(node.distance(p) <= hard + 1e-12);
+        }
+
+        // 两球在原点相切，x = 0 平面上等距时 h = 0.5，表面满足 sqrt(1 + y²) - 1 = k / 4
+        let expected_y = ((1.0 + k / 4.0).powi(2) - 1.0).sqrt();
+        let sdf = Sdf::new(
+            node,
+            AABB::new(Point3::new(-2.5, -1.5, -1.5), Point3::new(2.5, 1.5, 1.5)),
+            512,
+            1.0,
+            material(),
+        );
+        let ray = Ray::new(Point3::from_axis_y(5.0), Vec3::from_axis_y(-1.0), 0.0);
+        let rec = sdf.hit(&ray, Vec2::new(0.001, f64::INFINITY)).unwrap();
+        assert!(
+            (rec.t - (5.0 - expected_y)).abs() < 1e-3,
+            "{} != {}",
+            rec.t,
+            5.0 - expected_y
+        );
+        assert!(rec.normal.dot(Vec3::from_axis_y(1.0)) > 0.999);
+        assert!(rec.front_face);
+    }
+}
@@ -1,19 +1,27 @@
+use std::path::Path;
+
 use image::{DynamicImage, GenericImageView, ImageReader};
 
 use crate::{
     color::Color,
+    hittable::HitRecord,
     noise::PerlinNoise,
     vec::{Point3, Vec2},
 };
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, uv: Vec2, p: Point3) -> Color;
+    fn value_at(&self, record: &HitRecord) -> Color {
+        self.value(record.uv, record.p)
+    }
 }
 
 pub enum TextureEnum {
     SolidTexture(SolidTexture),
     CheckerTexture(CheckerTexture),
     NoiseTexture(NoiseTexture),
+    ImageTexture(ImageTexture),
+    VertexColorTexture(VertexColorTexture),
 }
 
 impl Texture for TextureEnum {
@@ -22,6 +30,14 @@ impl Texture for TextureEnum {
             TextureEnum::SolidTexture(t) => t.value(uv, p),
             TextureEnum::CheckerTexture(t) => t.value(uv, p),
             TextureEnum::NoiseTexture(t) => t.value(uv, p),
+            TextureEnum::ImageTexture(t) => t.value(uv, p),
+            TextureEnum::VertexColorTexture(t) => t.value(uv, p),
+        }
+    }
+    fn value_at(&self, record: &HitRecord) -> Color {
+        match self {
+            TextureEnum::VertexColorTexture(t) => t.value_at(record),
+            _ => self.value(record.uv, record.p),
         }
     }
 }
@@ -89,12 +105,20 @@ pub struct ImageTexture {
 
 impl ImageTexture {
     pub fn new(filename: &str) -> Self {
-        let image = ImageReader::open(format!("texture/{filename}"))
+        ImageTexture::from_path(Path::new("texture").join(filename))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let image = ImageReader::open(path)
             .expect("Failed to open image file.")
             .decode()
             .expect("Failed to decode image file.");
         ImageTexture { image }
     }
+
+    pub fn from_image(image: DynamicImage) -> Self {
+        ImageTexture { image }
+    }
 }
 
 impl Texture for ImageTexture {
@@ -103,10 +127,12 @@ impl Texture for ImageTexture {
         if self.image.width() == 0 || self.image.height() == 0 {
             return Color::new(0.0, 1.0, 1.0); // 青色
         }
-        let uv = Vec2::new(uv.0, 1.0 - uv.1);
+        // 超出 [0, 1] 的纹理坐标按重复平铺处理
+        let uv = Vec2::new(uv.0.rem_euclid(1.0), 1.0 - uv.1.rem_euclid(1.0));
+        let (width, height) = self.image.dimensions();
         let pixel = self.image.get_pixel(
-            (uv.0 * self.image.width() as f64) as u32,
-            (uv.1 * self.image.height() as f64) as u32,
+            ((uv.0 * width as f64) as u32).min(width - 1),
+            ((uv.1 * height as f64) as u32).min(height - 1),
         );
         Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64) / 255.0
     }
@@ -124,9 +150,35 @@ impl NoiseTexture {
     }
 }
 
+impl Default for NoiseTexture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Texture for NoiseTexture {
     fn value(&self, _: Vec2, p: Point3) -> Color {
         let scale = 10.0;
         Color::from_single((10.0 * p.2 + scale * self.noise.turb(p, 7)).sin() * 0.5 + 0.5)
     }
 }
+
+// 使用网格插值得到的顶点颜色，没有顶点颜色的表面显示为 fallback
+pub struct VertexColorTexture {
+    fallback: Color,
+}
+
+impl VertexColorTexture {
+    pub fn new(fallback: Color) -> Self {
+        VertexColorTexture { fallback }
+    }
+}
+
+impl Texture for VertexColorTexture {
+    fn value(&self, _: Vec2, _: Point3) -> Color {
+        self.fallback
+    }
+    fn value_at(&self, record: &HitRecord) -> Color {
+        record.vertex_color.unwrap_or(self.fallback)
+    }
+}
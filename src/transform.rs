@@ -0,0 +1,632 @@
+use crate::{
+    aabb::AABB,
+    math::mix,
+    matrix::{Mat33, Mat44},
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    // angle 为角度制
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let half = angle.to_radians() / 2.0;
+        let axis = axis.normalize() * half.sin();
+        Quaternion::new(half.cos(), axis.0, axis.1, axis.2)
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        Quaternion::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    // 由旋转矩阵求四元数，按最大的分量选择公式以保证数值稳定
+    pub fn from_mat33(m: Mat33) -> Self {
+        let m = |row: usize, col: usize| m.get(row, col);
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (m(2, 1) - m(1, 2)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+                (m(1, 0) - m(0, 1)) / s,
+            )
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (m(2, 1) - m(1, 2)) / s,
+                s / 4.0,
+                (m(0, 1) + m(1, 0)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+            )
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (m(0, 2) - m(2, 0)) / s,
+                (m(0, 1) + m(1, 0)) / s,
+                s / 4.0,
+                (m(1, 2) + m(2, 1)) / s,
+            )
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            Quaternion::new(
+                (m(1, 0) - m(0, 1)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+                (m(1, 2) + m(2, 1)) / s,
+                s / 4.0,
+            )
+        }
+        .normalize()
+    }
+
+    pub fn dot(self, q: Quaternion) -> f64 {
+        self.w * q.w + self.x * q.x + self.y * q.y + self.z * q.z
+    }
+
+    // 球面线性插值，总是沿较短的弧，因此相邻关键帧的旋转需小于 180 度
+    pub fn slerp(a: Quaternion, b: Quaternion, t: f64) -> Self {
+        let mut cos_theta = a.dot(b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion::new(-b.w, -b.x, -b.y, -b.z)
+        } else {
+            b
+        };
+        let (wa, wb) = if cos_theta > 0.9995 {
+            // 夹角很小时退化为线性插值
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(
+            wa * a.w + wb * b.w,
+            wa * a.x + wb * b.x,
+            wa * a.y + wb * b.y,
+            wa * a.z + wb * b.z,
+        )
+        .normalize()
+    }
+
+    pub fn to_mat33(self) -> Mat33 {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Mat33::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+}
+
+// 仿射变换，同时保存矩阵和它的逆
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    matrix: Mat44,
+    inverse: Mat44,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn new(matrix: Mat44) -> Self {
+        Transform::try_new(matrix)
+            .unwrap_or_else(|| panic!("Transform matrix {matrix:?} is not invertible."))
+    }
+
+    // 矩阵不可逆时返回 None
+    pub fn try_new(matrix: Mat44) -> Option<Self> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn identity() -> Self {
+        Transform {
+            matrix: Mat44::identity(),
+            inverse: Mat44::identity(),
+        }
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let translation = |v: Vec3| {
+            Mat44::new([
+                [1.0, 0.0, 0.0, v.0],
+                [0.0, 1.0, 0.0, v.1],
+                [0.0, 0.0, 1.0, v.2],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+        Transform {
+            matrix: translation(offset),
+            inverse: translation(-offset),
+        }
+    }
+
+    pub fn scale(factor: Vec3) -> Self {
+        Transform::new(Mat44::new([
+            [factor.0, 0.0, 0.0, 0.0],
+            [0.0, factor.1, 0.0, 0.0],
+            [0.0, 0.0, factor.2, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]))
+    }
+
+    pub fn rotate(axis: Vec3, angle: f64) -> Self {
+        Transform::from_quaternion(Quaternion::from_axis_angle(axis, angle))
+    }
+
+    // angles 为角度制，依次绕 X、Y、Z 轴旋转
+    pub fn euler(angles: Vec3) -> Self {
+        Transform::rotate(Vec3::from_axis_z(1.0), angles.2)
+            * Transform::rotate(Vec3::from_axis_y(1.0), angles.1)
+            * Transform::rotate(Vec3::from_axis_x(1.0), angles.0)
+    }
+
+    pub fn from_quaternion(q: Quaternion) -> Self {
+        let matrix = Mat44::from_mat33(q.to_mat33());
+        // 旋转矩阵的逆即为转置
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // 分解为 平移 * 旋转 * 缩放，缩放部分是对称矩阵，可以包含切变
+    fn decompose(&self) -> Decomposed {
+        let m = &self.matrix;
+        let translation = Vec3::new(m.get(0, 3), m.get(1, 3), m.get(2, 3));
+        let linear = m.to_mat33();
+        // 极分解：反复取矩阵与其逆转置的平均值，收敛到旋转部分
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let Some(inverse) = rotation.inverse() else {
+                break;
+            };
+            let next = Mat33::mix(rotation, inverse.transpose(), 0.5);
+            let difference = (0..3)
+                .flat_map(|row| (0..3).map(move |col| (row, col)))
+                .map(|(row, col)| (next.get(row, col) - rotation.get(row, col)).abs())
+                .fold(0.0, f64::max);
+            rotation = next;
+            if difference < 1e-12 {
+                break;
+            }
+        }
+        // 含镜像时把 -1 留给缩放部分，旋转部分保持为正交且行列式为 1
+        if rotation.determinant() < 0.0 {
+            rotation = rotation.map(|v| -v);
+        }
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_mat33(rotation),
+            scale: rotation.transpose() * linear,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat44 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // 法线用逆矩阵的转置变换，结果已归一化
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n).normalize()
+    }
+
+    pub fn transform_ray(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.transform_point(ray.origin),
+            self.transform_vector(ray.direction),
+            ray.time,
+        )
+    }
+
+    // 把世界空间的光线变换到物体空间，方向不归一化以保持 t 不变
+    pub fn inverse_transform_ray(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(ray.origin),
+            self.inverse.transform_vector(ray.direction),
+            ray.time,
+        )
+    }
+
+    // 变换包围盒的 8 个顶点后重新求包围盒
+    pub fn transform_aabb(&self, bbox: &AABB) -> AABB {
+        let mut min = Point3::from_single(f64::INFINITY);
+        let mut max = Point3::from_single(f64::NEG_INFINITY);
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let corner = Point3::new(
+                        mix(bbox[0].0, bbox[0].1, i as f64),
+                        mix(bbox[1].0, bbox[1].1, j as f64),
+                        mix(bbox[2].0, bbox[2].1, k as f64),
+                    );
+                    let p = self.transform_point(corner);
+                    min = min.min(p);
+                    max = max.max(p);
+                }
+            }
+        }
+        AABB::new(min, max)
+    }
+}
+
+// a * b 表示先应用 b 再应用 a
+impl std::ops::Mul for Transform {
+    type Output = Transform;
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+// 按时间排序的关键帧，时间与光线的 time 一致，取值在 [0, 1)
+#[derive(Debug, Clone)]
+pub struct Keyframes<T> {
+    keys: Vec<(f64, T)>,
+}
+
+impl<T: Copy> Keyframes<T> {
+    pub fn new(mut keys: Vec<(f64, T)>) -> Self {
+        assert!(!keys.is_empty(), "Keyframes need at least one key.");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Keyframes { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Keyframes {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = T> + '_ {
+        self.keys.iter().map(|key| key.1)
+    }
+
+    pub fn map<U>(&self, f: impl Fn(T) -> U) -> Keyframes<U> {
+        Keyframes {
+            keys: self
+                .keys
+                .iter()
+                .map(|&(time, value)| (time, f(value)))
+                .collect(),
+        }
+    }
+
+    // 快门区间的两端以及区间内的关键帧时间，相邻两个时间之间为线性插值
+    pub fn times_during(&self, shutter: Vec2) -> Vec<f64> {
+        let inner = self
+            .keys
+            .iter()
+            .map(|key| key.0)
+            .filter(|&time| time > shutter.0 && time < shutter.1);
+        std::iter::once(shutter.0)
+            .chain(inner)
+            .chain(std::iter::once(shutter.1))
+            .collect()
+    }
+
+    // 相邻的两个关键帧
+    pub fn segments(&self) -> impl Iterator<Item = (T, T)> + '_ {
+        self.keys.windows(2).map(|pair| (pair[0].1, pair[1].1))
+    }
+
+    // 在 time 所在的区间内插值，超出首尾关键帧时取端点的值
+    pub fn sample(&self, time: f64, interpolate: impl Fn(T, T, f64) -> T) -> T {
+        let index = self.keys.partition_point(|key| key.0 <= time);
+        if index == 0 {
+            return self.keys[0].1;
+        }
+        if index == self.keys.len() {
+            return self.keys[index - 1].1;
+        }
+        let ((t0, a), (t1, b)) = (self.keys[index - 1], self.keys[index]);
+        interpolate(a, b, (time - t0) / (t1 - t0))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: Mat33,
+}
+
+impl Decomposed {
+    fn mix(a: Decomposed, b: Decomposed, t: f64) -> Self {
+        Decomposed {
+            translation: Vec3::mix(a.translation, b.translation, t),
+            rotation: Quaternion::slerp(a.rotation, b.rotation, t),
+            scale: Mat33::mix(a.scale, b.scale, t),
+        }
+    }
+
+    // 逆矩阵由各部分的逆直接组合得到，不必对 4x4 矩阵求逆
+    fn compose(&self) -> Transform {
+        let rotation = self.rotation.to_mat33();
+        let scale_inverse = self
+            .scale
+            .inverse()
+            .expect("Interpolated transform is singular.");
+        let linear = Transform {
+            matrix: Mat44::from_mat33(rotation * self.scale),
+            inverse: Mat44::from_mat33(scale_inverse * rotation.transpose()),
+        };
+        Transform::translate(self.translation) * linear
+    }
+}
+
+// 随时间变化的变换，关键帧之间对平移、旋转和缩放分别插值
+pub struct AnimatedTransform {
+    keys: Keyframes<Transform>,
+    decomposed: Keyframes<Decomposed>,
+}
+
+impl AnimatedTransform {
+    // 每两个关键帧之间用于估计包围盒的采样数
+    const BOUND_SAMPLES: usize = 64;
+
+    pub fn new(keys: Keyframes<Transform>) -> Self {
+        let decomposed = keys.map(|transform| transform.decompose());
+        AnimatedTransform { keys, decomposed }
+    }
+
+    pub fn fixed(transform: Transform) -> Self {
+        AnimatedTransform::new(Keyframes::constant(transform))
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keys.is_animated()
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        if !self.is_animated() {
+            return self.keys.keys[0].1;
+        }
+        // 关键帧处直接使用原始变换，避免分解带来的误差
+        if let Some(&(_, transform)) = self.keys.keys.iter().find(|key| key.0 == time) {
+            return transform;
+        }
+        self.decomposed.sample(time, Decomposed::mix).compose()
+    }
+
+    // 整个快门时间内的包围盒，关键帧之间旋转的轨迹不是直线，用密集采样近似并略微放大
+    pub fn motion_aabb(&self, bbox: &AABB) -> AABB {
+        let mut result = self.keys.keys[0].1.transform_aabb(bbox);
+        for (a, b) in self.decomposed.segments() {
+            for i in 1..=Self::BOUND_SAMPLES {
+                let t = i as f64 / Self::BOUND_SAMPLES as f64;
+                let sample = Decomposed::mix(a, b, t).compose().transform_aabb(bbox);
+                result = AABB::from_aabb(&result, &sample);
+            }
+        }
+        if self.is_animated() {
+            let padding = Vec3::new(
+                result[0].1 - result[0].0,
+                result[1].1 - result[1].0,
+                result[2].1 - result[2].0,
+            ) * 0.01;
+            result = AABB::new(
+                Point3::new(result[0].0, result[1].0, result[2].0) - padding,
+                Point3::new(result[0].1, result[1].1, result[2].1) + padding,
+            );
+        }
+        result
+    }
+
+    // 快门区间内的包围盒，在区间内的每段关键帧之间采样
+    pub fn motion_aabb_during(&self, bbox: &AABB, shutter: Vec2) -> AABB {
+        if !self.is_animated() {
+            return self.motion_aabb(bbox);
+        }
+        let times = self.keys.times_during(shutter);
+        let mut result = self.at(times[0]).transform_aabb(bbox);
+        for pair in times.windows(2) {
+            for i in 1..=Self::BOUND_SAMPLES {
+                let time = mix(pair[0], pair[1], i as f64 / Self::BOUND_SAMPLES as f64);
+                result = AABB::from_aabb(&result, &self.at(time).transform_aabb(bbox));
+            }
+        }
+        let padding = Vec3::new(
+            result[0].1 - result[0].0,
+            result[1].1 - result[1].0,
+            result[2].1 - result[2].0,
+        ) * 0.01;
+        AABB::new(
+            Point3::new(result[0].0, result[1].0, result[2].0) - padding,
+            Point3::new(result[0].1, result[1].1, result[2].1) + padding,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::orthonormal_basis, random::m_random_range};
+
+    fn random_transform() -> Transform {
+        Transform::translate(Vec3::random_rage(-5.0..5.0))
+            * Transform::rotate(Vec3::random_rage(-1.0..1.0), m_random_range(-180.0..180.0))
+            * Transform::scale(Vec3::new(
+                m_random_range(0.2..3.0),
+                m_random_range(0.2..3.0),
+                m_random_range(0.2..3.0),
+            ))
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        for _ in 0..100 {
+            let transform = random_transform();
+            let normal = Vec3::random_rage(-1.0..1.0).normalize();
+            let (tangent, _) = orthonormal_basis(normal);
+            let normal = transform.transform_normal(normal);
+            let tangent = transform.transform_vector(tangent);
+            assert!(normal.dot(tangent).abs() < 1e-9 * tangent.length());
+            assert!((normal.length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn transformed_aabb_contains_transformed_corners() {
+        for _ in 0..100 {
+            let transform = random_transform();
+            let a = Vec3::random_rage(-3.0..3.0);
+            let b = Vec3::random_rage(-3.0..3.0);
+            let bbox = AABB::new(a, b);
+            let result = transform.transform_aabb(&bbox);
+            for i in 0..8 {
+                let corner = Point3::new(
+                    [a.0, b.0][i & 1],
+                    [a.1, b.1][(i >> 1) & 1],
+                    [a.2, b.2][(i >> 2) & 1],
+                );
+                let p = transform.transform_point(corner);
+                for axis in 0..3 {
+                    assert!(result[axis].0 <= p[axis] + 1e-9 && p[axis] <= result[axis].1 + 1e-9);
+                }
+            }
+        }
+    }
+
+    fn assert_close(a: &Transform, b: &Transform) {
+        for row in 0..4 {
+            for col in 0..4 {
+                let (x, y) = (a.matrix().get(row, col), b.matrix().get(row, col));
+                assert!((x - y).abs() < 1e-9, "{:?} != {:?}", a.matrix(), b.matrix());
+                let (x, y) = (
+                    a.inverse().matrix().get(row, col),
+                    b.inverse().matrix().get(row, col),
+                );
+                assert!(
+                    (x - y).abs() < 1e-9,
+                    "{:?} != {:?}",
+                    a.inverse(),
+                    b.inverse()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decompose_compose_round_trip() {
+        for i in 0..100 {
+            let mut transform = random_transform();
+            // 一半的变换带镜像
+            if i % 2 == 0 {
+                transform = transform * Transform::scale(Vec3::new(1.0, -1.0, 1.0));
+            }
+            let decomposed = transform.decompose();
+            let rotation = decomposed.rotation.to_mat33();
+            assert!((rotation.determinant() - 1.0).abs() < 1e-9);
+            assert_close(&decomposed.compose(), &transform);
+        }
+    }
+
+    #[test]
+    fn interpolation_returns_endpoint_keyframes() {
+        for _ in 0..100 {
+            let (a, b) = (random_transform(), random_transform());
+            let (da, db) = (a.decompose(), b.decompose());
+            assert_close(&Decomposed::mix(da, db, 0.0).compose(), &a);
+            assert_close(&Decomposed::mix(da, db, 1.0).compose(), &b);
+            // 首尾关键帧之外取端点的值
+            let animated = AnimatedTransform::new(Keyframes::new(vec![(0.2, a), (0.8, b)]));
+            assert_close(&animated.at(0.0), &a);
+            assert_close(&animated.at(1.0), &b);
+        }
+    }
+
+    #[test]
+    fn motion_aabb_contains_sampled_boxes() {
+        for _ in 0..20 {
+            let keys = (0..3)
+                .map(|i| (i as f64 / 2.0, random_transform()))
+                .collect();
+            let animated = AnimatedTransform::new(Keyframes::new(keys));
+            let bbox = AABB::new(Vec3::random_rage(-2.0..0.0), Vec3::random_rage(0.0..2.0));
+            let motion = animated.motion_aabb(&bbox);
+            let shutter = Vec2::new(0.1, 0.7);
+            let during = animated.motion_aabb_during(&bbox, shutter);
+            for _ in 0..200 {
+                let time = m_random_range(0.0..1.0);
+                let sample = animated.at(time).transform_aabb(&bbox);
+                let inside = |outer: &AABB| {
+                    (0..3).all(|axis| {
+                        outer[axis].0 <= sample[axis].0 + 1e-9
+                            && sample[axis].1 <= outer[axis].1 + 1e-9
+                    })
+                };
+                assert!(inside(&motion), "{sample:?} is outside {motion:?}");
+                if time >= shutter.0 && time <= shutter.1 {
+                    assert!(inside(&during), "{sample:?} is outside {during:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is not invertible")]
+    fn singular_transform_panics() {
+        Transform::scale(Vec3::new(1.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    vec::{Point3, Vec2, Vec3},
+};
+
+// 稠密的体素数据，每个体素存材质编号，0 表示空，k 表示 materials[k - 1]
+pub struct VoxelData {
+    pub size: [usize; 3],
+    pub voxels: Vec<u16>,
+}
+
+impl VoxelData {
+    pub fn new(size: [usize; 3]) -> Self {
+        VoxelData {
+            size,
+            voxels: vec![0; size[0] * size[1] * size[2]],
+        }
+    }
+
+    fn index(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.size[1] + cell[1]) * self.size[0] + cell[0]
+    }
+
+    pub fn get(&self, cell: [usize; 3]) -> u16 {
+        self.voxels[self.index(cell)]
+    }
+
+    pub fn set(&mut self, cell: [usize; 3], material: u16) {
+        assert!(
+            (0..3).all(|axis| cell[axis] < self.size[axis]),
+            "Voxel {cell:?} is outside the grid of size {:?}.",
+            self.size
+        );
+        let index = self.index(cell);
+        self.voxels[index] = material;
+    }
+}
+
+// 以 origin 为最小角、边长为 voxel_size 的体素网格，用 Amanatides-Woo 3D-DDA 遍历
+pub struct VoxelGrid<M: Material> {
+    data: VoxelData,
+    origin: Point3,
+    voxel_size: f64,
+    materials: Vec<Arc<M>>,
+    bbox: AABB,
+}
+
+impl<M: Material> VoxelGrid<M> {
+    pub fn new(data: VoxelData, origin: Point3, voxel_size: f64, materials: Vec<Arc<M>>) -> Self {
+        assert!(
+            data.voxels.iter().all(|&v| (v as usize) <= materials.len()),
+            "Voxel material index out of range."
+        );
+        // 包围盒只包住非空的体素
+        let mut lower = [usize::MAX; 3];
+        let mut upper = [0; 3];
+        for z in 0..data.size[2] {
+            for y in 0..data.size[1] {
+                for x in 0..data.size[0] {
+                    if data.get([x, y, z]) != 0 {
+                        for (axis, v) in [x, y, z].into_iter().enumerate() {
+                            lower[axis] = lower[axis].min(v);
+                            upper[axis] = upper[axis].max(v + 1);
+                        }
+                    }
+                }
+            }
+        }
+        assert!(lower[0] < upper[0], "Voxel grid is empty.");
+        let corner = |cell: [usize; 3]| {
+            origin + Vec3::new(cell[0] as f64, cell[1] as f64, cell[2] as f64) * voxel_size
+        };
+        let bbox = AABB::new(corner(lower), corner(upper));
+        VoxelGrid {
+            data,
+            origin,
+            voxel_size,
+            materials,
+            bbox,
+        }
+    }
+
+    // 网格外视为空
+    fn value(&self, cell: [i64; 3]) -> u16 {
+        if (0..3).all(|axis| cell[axis] >= 0 && (cell[axis] as usize) < self.data.size[axis]) {
+            self.data.get(cell.map(|v| v as usize))
+        } else {
+            0
+        }
+    }
+
+    // 穿过垂直于 axis 的面，从 from 进入 to，法线总是背向光线
+    fn record<'a>(
+        &'a self,
+        ray: &Ray,
+        t: f64,
+        axis: usize,
+        step: i64,
+        from: u16,
+        to: u16,
+    ) -> HitRecord<'a> {
+        let p = ray.at(t);
+        let mut normal = [0.0; 3];
+        normal[axis] = -step as f64;
+        // 进入非空体素时使用其材质，否则为离开 from
+        let (material, front_face) = if to != 0 { (to, true) } else { (from, false) };
+        let local = (p - self.origin) / self.voxel_size;
+        let fract = |axis: usize| local[axis] - local[axis].floor();
+        HitRecord {
+            p,
+            normal: Vec3::new(normal[0], normal[1], normal[2]),
+            t,
+            material: self.materials[material as usize - 1].as_ref(),
+            front_face,
+            uv: Vec2::new(fract((axis + 1) % 3), fract((axis + 2) % 3)),
+            tangent: None,
+            vertex_color: None,
+        }
+    }
+}
+
+impl<M: Material + 'static> Hittable for VoxelGrid<M> {
+    fn hit<'a>(&'a self, ray: &Ray, t_range: Vec2) -> Option<HitRecord<'a>> {
+        let range = self.bbox.clip(ray, t_range)?;
+        let d = ray.direction;
+        let step = [0, 1, 2].map(|axis| if d[axis] > 0.0 { 1 } else { -1 });
+        // 光线从外部进入包围盒时，进入面为进入参数最大的轴
+        let entry_axis = (0..3)
+            .filter(|&axis| d[axis] != 0.0)
+            .max_by(|&a, &b| {
+                let near = |axis: usize| {
+                    let bound = if d[axis] > 0.0 {
+                        self.bbox[axis].0
+                    } else {
+                        self.bbox[axis].1
+                    };
+                    (bound - ray.origin[axis]) / d[axis]
+                };
+                near(a).total_cmp(&near(b))
+            })
+            .unwrap_or(0);
+        let from_outside = range.0 > t_range.0;
+
+        let local = (ray.at(range.0) - self.origin) / self.voxel_size;
+        let mut cell = [0, 1, 2].map(|axis| local[axis].floor() as i64);
+        if from_outside {
+            // 起点恰好在面上，浮点误差可能让它落在外侧
+            cell[entry_axis] = if step[entry_axis] > 0 {
+                ((self.bbox[entry_axis].0 - self.origin[entry_axis]) / self.voxel_size).round()
+                    as i64
+            } else {
+                ((self.bbox[entry_axis].1 - self.origin[entry_axis]) / self.voxel_size).round()
+                    as i64
+                    - 1
+            };
+        }
+        let t_delta = [0, 1, 2].map(|axis| (self.voxel_size / d[axis]).abs());
+        let mut t_max = [0, 1, 2].map(|axis| {
+            if d[axis] == 0.0 {
+                return f64::INFINITY;
+            }
+            let boundary = cell[axis] + if step[axis] > 0 { 1 } else { 0 };
+            (self.origin[axis] + boundary as f64 * self.voxel_size - ray.origin[axis]) / d[axis]
+        });
+
+        let current = if from_outside { 0 } else { self.value(cell) };
+        let (mut t, mut axis) = (range.0, entry_axis);
+        loop {
+            let value = self.value(cell);
+            if value != current {
+                return Some(self.record(ray, t, axis, step[axis], current, value));
+            }
+            axis = (0..3)
+                .min_by(|&a, &b| t_max[a].total_cmp(&t_max[b]))
+                .unwrap();
+            t = t_max[axis];
+            // 离开包围盒后不会再有非空体素，除非当前仍在体素内部
+            if t > t_range.1 || (t > range.1 && current == 0) {
+                return None;
+            }
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+    }
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        random::{m_random, m_random_range},
+        texture::SolidTexture,
+    };
+
+    #[test]
+    fn dda_matches_per_voxel_boxes() {
+        let size = [7, 5, 6];
+        let mut data = VoxelData::new(size);
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    if m_random::<f64>() < 0.15 {
+                        data.set([x, y, z], m_random_range(1..=3));
+                    }
+                }
+            }
+        }
+        data.set([3, 2, 3], 1);
+        let (origin, voxel_size) = (Point3::new(-1.0, 0.5, -2.0), 0.4);
+        let voxel_box = |cell: [usize; 3]| {
+            let corner = |offset: usize| {
+                origin
+                    + Vec3::new(
+                        (cell[0] + offset) as f64,
+                        (cell[1] + offset) as f64,
+                        (cell[2] + offset) as f64,
+                    ) * voxel_size
+            };
+            AABB::new(corner(0), corner(1))
+        };
+        let filled: Vec<([usize; 3], u16)> = (0..size[2])
+            .flat_map(|z| (0..size[1]).flat_map(move |y| (0..size[0]).map(move |x| [x, y, z])))
+            .map(|cell| (cell, data.get(cell)))
+            .filter(|&(_, value)| value != 0)
+            .collect();
+        let materials = (0..3)
+            .map(|i| {
+                Arc::new(Lambertian::new(SolidTexture::new(Vec3::from_single(
+                    i as f64,
+                ))))
+            })
+            .collect::<Vec<_>>();
+        let grid = VoxelGrid::new(data, origin, voxel_size, materials.clone());
+
+        // 光线从网格外出发，最近的命中为各个非空体素包围盒进入参数的最小值
+        let t_range = Vec2::new(0.001, f64::INFINITY);
+        let bbox = grid.bounding_box().clone();
+        let center = (Point3::new(bbox[0].0, bbox[1].0, bbox[2].0)
+            + Point3::new(bbox[0].1, bbox[1].1, bbox[2].1))
+            / 2.0;
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let start = center + Vec3::random_rage(-1.0..1.0).normalize() * 6.0;
+            let target = center + Vec3::random_rage(-1.5..1.5);
+            let ray = Ray::new(start, target - start, 0.0);
+            let expected = filled
+                .iter()
+                .filter_map(|&(cell, value)| {
+                    voxel_box(cell)
+                        .clip(&ray, t_range)
+                        .map(|range| (range.0, value))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            let actual = grid.hit(&ray, t_range);
+            match (&actual, expected) {
+                (Some(rec), Some((t, value))) => {
+                    assert!((rec.t - t).abs() < 1e-9, "{} != {t}", rec.t);
+                    assert!(rec.front_face);
+                    assert!(rec.normal.dot(ray.direction) < 0.0);
+                    // 恰好打在两个体素的棱上时材质可能不同
+                    let material = materials[value as usize - 1].as_ref() as *const _ as *const ();
+                    let hit = rec.material as *const dyn Material as *const ();
+                    let on_edge = (0..3)
+                        .filter(|&axis| {
+                            let local = (rec.p[axis] - origin[axis]) / voxel_size;
+                            (local - local.round()).abs() < 1e-6
+                        })
+                        .count()
+                        > 1;
+                    assert!(on_edge || material == hit);
+                }
+                (None, None) => {}
+                _ => panic!("{:?} != {expected:?} for {ray:?}", actual.map(|rec| rec.t)),
+            }
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 1000);
+    }
+}